@@ -1,57 +1,28 @@
-use std::fs;
+use intcode::{load, Goal, Linear, Patch, Program, Symbolic};
 
-fn parse(s: &str) -> usize {
-    s.trim().parse().unwrap()
-}
-
-fn intcode(input: &Vec<usize>, a: usize, b: usize) -> usize {
-    let mut i = 0;
-    let mut tokens = input.to_vec();
-
-    tokens[1] = a;
-    tokens[2] = b;
-
-    loop {
-        match tokens[i] {
-            1 => {
-                let a = tokens[tokens[i+1]];
-                let b = tokens[tokens[i+2]];
-                let c = tokens[i+3];
-                tokens[c] = a+b;
-                i += 4;
-            }
-            2 => {
-                let a = tokens[tokens[i+1]];
-                let b = tokens[tokens[i+2]];
-                let c = tokens[i+3];
-                tokens[c] = a*b;
-                i += 4;
-            }
-            99 => {
-                break;
-            }
-            _ => {
-                println!("Oops: {} {}", i, tokens[i]);
-            }
-        }
-    }
-
-    tokens[0]
+fn alarm() -> Patch {
+    Patch { name: "1202 program alarm".to_string(), writes: vec![(1, 12), (2, 2)] }
 }
 
 fn main() {
-    let tokens:Vec<usize> = fs::read_to_string("day2.txt").unwrap().split(",").map(parse).collect();
-    let ret = intcode(&tokens, 12, 2);
+    let tokens = match load("day2.txt") {
+        Ok(t) => t,
+        Err(e) => return eprintln!("day2.txt: {}", e),
+    };
+    let mut p = Program::new(&tokens);
+    p.patch(&alarm());
+    p.intcode().unwrap();
+    let ret = p.get(0);
 
     println!("{}", ret);
 
-    for a in 0..=99 {
-        for b in 0..=99 {
-            let ret = intcode(&tokens, a, b);
-            if ret == 19690720 {
-                println!("100 * {} + {} = {}", a, b, 100*a+b);
-                break;
-            }
-        }
+    let mut sym = Symbolic::new(&tokens);
+    let noun = sym.symbol("noun", 0..=99);
+    let verb = sym.symbol("verb", 0..=99);
+    sym.set(1, noun);
+    sym.set(2, verb);
+    if let Some(p) = sym.solve(Goal::Cell(0, 19690720), &Linear).unwrap() {
+        let (a, b) = (p[0], p[1]);
+        println!("100 * {} + {} = {}", a, b, 100*a+b);
     }
 }
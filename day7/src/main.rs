@@ -1,202 +1,34 @@
+use intcode::{argmax_by, link, load, Limits, Pipeline, Program};
 use itertools::Itertools;
-use std::fs;
-use std::i32;
-use std::collections::VecDeque;
 
-fn parse(s: &str) -> i32 {
-    s.trim().parse().unwrap()
-}
-
-fn position(t: &Vec<i32>, i: usize) -> i32 {
-    t[t[i] as usize] as i32
-}
-
-fn immediate(t: &Vec<i32>, i: usize) -> i32 {
-    t[i] as i32
-}
-
-fn mode(m: i32) -> fn(&Vec<i32>, usize) -> i32 {
-    match m {
-        0 => position,
-        1 => immediate,
-        _ => panic!("{}", m),
-    }
-}
-
-struct Program {
-    orig: Vec<i32>,
-    tokens: Vec<i32>,
-    inputs: VecDeque<i32>,
-    output: i32,
-    i: usize,
-    halted: bool,
-}
-
-impl Program {
-    fn new(t: &Vec<i32>) -> Program {
-        Program {
-            orig: t.to_vec(),
-            tokens: t.to_vec(),
-            inputs: VecDeque::new(),
-            output: i32::MIN,
-            i: 0,
-            halted: false,
-        }
-    }
-
-    fn reset(&mut self) {
-        self.tokens = self.orig.to_vec();
-        self.i = 0;
-        self.inputs = VecDeque::new();
-        self.output = i32::MIN;
-        self.halted = false;
-    }
-
-    fn op(
-        &mut self,
-        a_mode: fn(&Vec<i32>, usize) -> i32,
-        b_mode: fn(&Vec<i32>, usize) -> i32,
-        o: fn(a: i32, b: i32) -> i32,
-    ) {
-        let a = a_mode(&self.tokens, self.i + 1);
-        let b = b_mode(&self.tokens, self.i + 2);
-        let c = self.tokens[self.i + 3] as usize;
-        self.tokens[c] = o(a, b);
-        self.i += 4;
-    }
+// An amplifier that runs this many instructions is stuck in a loop.
+const LIMIT: usize = 100_000;
 
-    fn branch(
-        &mut self,
-        a_mode: fn(&Vec<i32>, usize) -> i32,
-        b_mode: fn(&Vec<i32>, usize) -> i32,
-        o: fn(a: i32) -> bool,
-    ) {
-        let a = a_mode(&self.tokens, self.i + 1);
-        let b = b_mode(&self.tokens, self.i + 2);
-        if o(a) {
-            self.i = b as usize;
-        } else {
-            self.i += 3;
-        }
+fn run(t: &[i128], order: &[i128]) -> i128 {
+    let names = ["A", "B", "C", "D", "E"];
+    let limits = Limits { instructions: Some(LIMIT), ..Limits::default() };
+    let mut amps = Pipeline::new();
+    for (name, x) in names.iter().zip(order) {
+        amps.node(name, Program::new(t)).phase(name, *x).limits(name, limits);
     }
-    fn test(
-        &mut self,
-        a_mode: fn(&Vec<i32>, usize) -> i32,
-        b_mode: fn(&Vec<i32>, usize) -> i32,
-        o: fn(a: i32, b: i32) -> bool,
-    ) {
-        let a = a_mode(&self.tokens, self.i + 1);
-        let b = b_mode(&self.tokens, self.i + 2);
-        let c = self.tokens[self.i + 3] as usize;
-        if o(a, b) {
-            self.tokens[c] = 1;
-        } else {
-            self.tokens[c] = 0;
-        }
-        self.i += 4;
-    }
-
-    fn intcode(&mut self) {
-        loop {
-            let t = self.tokens[self.i];
-            let opcode = t % 100;
-            let a_mode = mode((t / 100) % 10);
-            let b_mode = mode((t / 1000) % 10);
-
-            //        println!("a_mode = {}, t = {}, opcode = {}", (t / 100) % 10, t, opcode);
-
-            match opcode {
-                1 => {
-                    self.op(a_mode, b_mode, |a, b| a + b);
-                }
-                2 => {
-                    self.op(a_mode, b_mode, |a, b| a * b);
-                }
-                3 => {
-                    let a = self.tokens[self.i + 1] as usize;
-                    self.tokens[a] = self.inputs.pop_front().unwrap();
-                    self.i += 2;
-                }
-                4 => {
-                    self.output = a_mode(&self.tokens, self.i + 1);
-                    self.i += 2;
-                    return;
-                }
-                5 => {
-                    self.branch(a_mode, b_mode, |a| a != 0);
-                }
-                6 => {
-                    self.branch(a_mode, b_mode, |a| a == 0);
-                }
-                7 => {
-                    self.test(a_mode, b_mode, |a, b| a < b);
-                }
-                8 => {
-                    self.test(a_mode, b_mode, |a, b| a == b);
-                }
-                99 => {
-                    //println!("Halt!");
-                    self.halted = true;
-                    return;
-                }
-                _ => {
-                    println!("Oops: {} {}", self.i, self.tokens[self.i]);
-                }
-            }
-        }
-    }
-}
-
-fn run(programs: &mut Vec<Program>, order: &Vec<i32>) -> i32 {
-    programs.iter_mut().for_each(|x|x.reset());
-    for (x,p) in order.iter().zip(programs.iter_mut()) {
-        p.inputs.push_back(*x);
-    }
-
-    //println!("Order:{:?}", order);
-    let mut output = 0;
-    let mut i = 0;
-    while !programs.iter().any(|x|x.halted) {
-        i += 1;
-        if i > 10000 {
-            panic!("Loops");
-        }
-        for p in programs.iter_mut() {
-            p.inputs.push_back(output);
-            p.intcode();
-            output = p.output;
-        }
-    }
-    //println!("Order:{:?}={}", order, output);
-    output
+    amps.ring(&names).send(&link("E", "A"), 0);
+    amps.run().unwrap();
+    amps.last(&link("E", "A")).unwrap()
 }
 
-fn one(t: &Vec<i32>) -> i32 {
-    let mut programs:Vec<Program> = Vec::new();
-
-    for _ in 0..5 {
-        programs.push(Program::new(t));
-    }
-
-    (0..=4).permutations(5).map(|x| run(&mut programs, &x)).max().unwrap()
+fn one(t: &[i128]) -> i128 {
+    argmax_by((0..=4).permutations(5), |x| Some(run(t, x))).unwrap().1
 }
 
-fn two(t: &Vec<i32>) -> i32 {
-    let mut programs:Vec<Program> = Vec::new();
-
-    for _ in 0..5 {
-        programs.push(Program::new(t));
-    }
-
-    (5..=9).permutations(5).map(|x| run(&mut programs, &x)).max().unwrap()
+fn two(t: &[i128]) -> i128 {
+    argmax_by((5..=9).permutations(5), |x| Some(run(t, x))).unwrap().1
 }
 
 fn main() {
-    let tokens: Vec<i32> = fs::read_to_string("day7.txt")
-        .unwrap()
-        .split(",")
-        .map(parse)
-        .collect();
+    let tokens = match load("day7.txt") {
+        Ok(t) => t,
+        Err(e) => return eprintln!("day7.txt: {}", e),
+    };
     let ret = one(&tokens);
     println!("one = {}", ret);
     let ret = two(&tokens);
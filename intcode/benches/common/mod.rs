@@ -0,0 +1,112 @@
+// Helpers shared by the benchmarks.
+
+use intcode::{Compiled, Program, State};
+use std::cmp::Ordering;
+
+pub const RUNS: u32 = 5;
+
+/// Something a puzzle can be played on: an interpreted or compiled program.
+pub trait Machine {
+    fn input(&mut self, x: i128);
+    fn run(&mut self) -> State;
+}
+
+impl Machine for Program {
+    fn input(&mut self, x: i128) {
+        self.inputs.push_back(x);
+    }
+
+    fn run(&mut self) -> State {
+        Program::run(self).unwrap()
+    }
+}
+
+impl Machine for Compiled {
+    fn input(&mut self, x: i128) {
+        Compiled::input(self, x);
+    }
+
+    fn run(&mut self) -> State {
+        Compiled::run(self).unwrap()
+    }
+}
+
+/// Plays a whole puzzle and returns its answer.
+pub type Driver = fn(&mut dyn Machine) -> i128;
+
+pub fn load(day: &str) -> Option<Vec<i128>> {
+    let path = format!("{}/../{}/{}.txt", env!("CARGO_MANIFEST_DIR"), day, day);
+    intcode::load(path).ok()
+}
+
+/// Stands in for BOOST when there's no day9 input: a naive recursive
+/// Fibonacci of ten times its input, which like BOOST spends its time in
+/// calls whose frames sit relative to `rb`.
+pub const FIB: &str = "
+            in [n]
+            mul [n], #10, [n]
+            arb #stack
+            add [n], #0, [rb+1]
+            add #back, #0, [rb]
+            jt #1, #fib
+    back:   out [rb+2]
+            hlt
+
+    ; [rb] is the return address, [rb+1] the argument and [rb+2] the result
+    fib:    lt [rb+1], #2, [rb+3]
+            jf [rb+3], #recurse
+            add [rb+1], #0, [rb+2]
+            jt #1, [rb]
+    recurse:
+            add [rb+1], #-1, [rb+5]
+            add #ret1, #0, [rb+4]
+            arb #4
+            jt #1, #fib
+    ret1:   arb #-4
+            add [rb+6], #0, [rb+3]
+            add [rb+1], #-2, [rb+5]
+            add #ret2, #0, [rb+4]
+            arb #4
+            jt #1, #fib
+    ret2:   arb #-4
+            add [rb+3], [rb+6], [rb+2]
+            jt #1, [rb]
+    n:      db 0
+    stack:  db 0
+";
+
+// day9 part two: a single run with input 2
+pub fn boost(m: &mut dyn Machine) -> i128 {
+    m.input(2);
+    let mut last = 0;
+    while let State::Output(o) = m.run() {
+        last = o;
+    }
+    last
+}
+
+// day13 part two: play the whole game, keeping the paddle under the ball
+pub fn arcade(m: &mut dyn Machine) -> i128 {
+    let (mut ball, mut paddle, mut score) = (0, 0, 0);
+    let mut out = Vec::new();
+    loop {
+        match m.run() {
+            State::NeedsInput => m.input(match ball.cmp(&paddle) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            }),
+            State::Output(o) => out.push(o),
+            State::Halted => return score,
+        }
+        if let [x, y, c] = out[..] {
+            match (x, y, c) {
+                (-1, 0, s) => score = s,
+                (x, _, 3) => paddle = x,
+                (x, _, 4) => ball = x,
+                _ => {}
+            }
+            out.clear();
+        }
+    }
+}
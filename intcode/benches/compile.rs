@@ -0,0 +1,50 @@
+mod common;
+
+use common::{arcade, boost, load, Driver, Machine, FIB, RUNS};
+use intcode::{assemble, Compiled, Program};
+use std::time::{Duration, Instant};
+
+type Runner = fn(Program) -> Box<dyn Machine>;
+
+// Built with `--features uncached`, the interpreter decodes every
+// instruction afresh, giving the baseline both caching and compiling
+// are measured against.
+const INTERP: &str = if cfg!(feature = "uncached") {
+    "uncached"
+} else {
+    "interp"
+};
+
+fn bench(name: &str, t: &[i128], patch: &[(i128, i128)], f: Driver) {
+    let runners: [(&str, Runner); 2] = [
+        (INTERP, |p| Box::new(p)),
+        ("compiled", |p| Box::new(Compiled::new(p))),
+    ];
+    let mut results = Vec::new();
+    for (runner, make) in runners.iter() {
+        let mut total = Duration::new(0, 0);
+        for _ in 0..RUNS {
+            let mut p = Program::new(t);
+            for (a, x) in patch {
+                p.set(*a, *x);
+            }
+            let start = Instant::now();
+            let mut m = make(p);
+            results.push(f(&mut *m));
+            total += start.elapsed();
+        }
+        println!("{:<8} {:<8} {:?}", name, runner, total / RUNS);
+    }
+    assert!(results.iter().all(|r| *r == results[0]));
+}
+
+fn main() {
+    match load("day9") {
+        Some(t) => bench("day9", &t, &[], boost),
+        None => bench("fib", &assemble(FIB).unwrap(), &[], boost),
+    }
+    match load("day13") {
+        Some(t) => bench("day13", &t, &[(0, 2)], arcade),
+        None => println!("{:<8} skipped, no day13/day13.txt", "day13"),
+    }
+}
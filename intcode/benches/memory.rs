@@ -0,0 +1,39 @@
+mod common;
+
+use common::{arcade, boost, load, Driver, FIB, RUNS};
+use intcode::{assemble, DenseMemory, HashMemory, Memory, PagedMemory, Program};
+use std::time::{Duration, Instant};
+
+type Backend = fn() -> Box<dyn Memory>;
+
+fn bench(name: &str, t: &[i128], patch: &[(i128, i128)], f: Driver) {
+    let backends: [(&str, Backend); 3] = [
+        ("hash", || Box::new(HashMemory::new())),
+        ("dense", || Box::new(DenseMemory::new())),
+        ("paged", || Box::new(PagedMemory::new())),
+    ];
+    for (backend, memory) in backends.iter() {
+        let mut total = Duration::new(0, 0);
+        for _ in 0..RUNS {
+            let mut p = Program::with_memory(t, memory());
+            for (a, x) in patch {
+                p.set(*a, *x);
+            }
+            let start = Instant::now();
+            f(&mut p);
+            total += start.elapsed();
+        }
+        println!("{:<8} {:<6} {:?}", name, backend, total / RUNS);
+    }
+}
+
+fn main() {
+    match load("day9") {
+        Some(t) => bench("day9", &t, &[], boost),
+        None => bench("fib", &assemble(FIB).unwrap(), &[], boost),
+    }
+    match load("day13") {
+        Some(t) => bench("day13", &t, &[(0, 2)], arcade),
+        None => println!("{:<8} skipped, no day13/day13.txt", "day13"),
+    }
+}
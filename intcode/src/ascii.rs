@@ -0,0 +1,200 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+
+use crate::{IntcodeError, Program, State};
+
+/// Something an ASCII program printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Text {
+    Line(String),
+    /// A value outside the ASCII range, usually the answer.
+    Value(i128),
+}
+
+/// A command holding a character outside ASCII, which can't be sent
+/// without looking like one of the program's out-of-band values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotAscii(pub char);
+
+impl fmt::Display for NotAscii {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` isn't ASCII", self.0)
+    }
+}
+
+impl Error for NotAscii {}
+
+/// Talks to a program that reads and writes text one character code at a
+/// time.
+pub struct Ascii {
+    pub program: Program,
+    line: String,
+}
+
+impl Ascii {
+    pub fn new(program: Program) -> Ascii {
+        Ascii {
+            program,
+            line: String::new(),
+        }
+    }
+
+    /// Queues `command` followed by a newline. Nothing is queued if any of
+    /// it isn't ASCII.
+    pub fn send(&mut self, command: &str) -> Result<(), NotAscii> {
+        if let Some(c) = command.chars().find(|c| !c.is_ascii()) {
+            return Err(NotAscii(c));
+        }
+        let codes = command.chars().chain("\n".chars()).map(|c| c as i128);
+        self.program.inputs.extend(codes);
+        Ok(())
+    }
+
+    /// Runs until the program wants input or halts, returning what it
+    /// printed meanwhile. Output is split into lines, and a line left
+    /// unfinished when it stops, like a prompt, is returned as well.
+    pub fn run(&mut self) -> Result<(Vec<Text>, State), IntcodeError> {
+        let mut out = Vec::new();
+        loop {
+            match self.program.run()? {
+                State::Output(10) => out.push(Text::Line(self.line.split_off(0))),
+                State::Output(o) if (0..=127).contains(&o) => self.line.push(o as u8 as char),
+                State::Output(o) => {
+                    if !self.line.is_empty() {
+                        out.push(Text::Line(self.line.split_off(0)));
+                    }
+                    out.push(Text::Value(o));
+                }
+                s => {
+                    if !self.line.is_empty() {
+                        out.push(Text::Line(self.line.split_off(0)));
+                    }
+                    return Ok((out, s));
+                }
+            }
+        }
+    }
+
+    /// Runs with a human at the keyboard: whatever the program prints goes
+    /// to `output`, and each time it wants input a line is read from
+    /// `input`. Stops when the program halts or `input` runs out. A line
+    /// that isn't ASCII is refused and another one read.
+    pub fn interact<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> io::Result<State> {
+        loop {
+            let (text, s) = self.run().map_err(io::Error::other)?;
+            for t in text {
+                match t {
+                    Text::Line(l) => writeln!(output, "{}", l)?,
+                    Text::Value(x) => writeln!(output, "[{}]", x)?,
+                }
+            }
+            output.flush()?;
+            if s == State::Halted {
+                return Ok(s);
+            }
+
+            loop {
+                let mut command = String::new();
+                if input.read_line(&mut command)? == 0 {
+                    return Ok(s);
+                }
+                match self.send(command.trim_end_matches(['\r', '\n'])) {
+                    Ok(()) => break,
+                    Err(e) => writeln!(output, "{}", e)?,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // prompts, echoes a line back in capitals, then prints 1000
+    const SHOUT: &str = "
+            out #62
+    loop:   in [c]
+            eq [c], #10, [t]
+            jt [t], #done
+            lt [c], #97, [t]
+            jt [t], #print
+            add [c], #-32, [c]
+    print:  out [c]
+            jt #1, #loop
+    done:   out #10
+            out #1000
+            hlt
+    c:      db 0
+    t:      db 0
+    ";
+
+    #[test]
+    fn test_lines() {
+        let mut a = Ascii::new(Program::new(&assemble(SHOUT).unwrap()));
+
+        assert_eq!(
+            (vec![Text::Line(">".to_string())], State::NeedsInput),
+            a.run().unwrap()
+        );
+        a.send("hello").unwrap();
+        assert_eq!(
+            (
+                vec![Text::Line("HELLO".to_string()), Text::Value(1000)],
+                State::Halted
+            ),
+            a.run().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_not_ascii() {
+        let mut a = Ascii::new(Program::new(&assemble(SHOUT).unwrap()));
+
+        assert_eq!(Err(NotAscii('é')), a.send("café"));
+        assert!(a.program.inputs.is_empty());
+
+        let mut out = Vec::new();
+        a.interact("café\ncafe\n".as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            ">\n`é` isn't ASCII\nCAFE\n[1000]\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_value_splits_line() {
+        let mut a = Ascii::new(Program::new(&[104, 65, 104, -1, 104, 66, 104, 10, 99]));
+        let (text, _) = a.run().unwrap();
+
+        assert_eq!(
+            vec![
+                Text::Line("A".to_string()),
+                Text::Value(-1),
+                Text::Line("B".to_string())
+            ],
+            text
+        );
+    }
+
+    #[test]
+    fn test_interact() {
+        let mut a = Ascii::new(Program::new(&assemble(SHOUT).unwrap()));
+        let mut out = Vec::new();
+        let s = a.interact(&b"quiet please\n"[..], &mut out).unwrap();
+
+        assert_eq!(State::Halted, s);
+        assert_eq!(">\nQUIET PLEASE\n[1000]\n", String::from_utf8(out).unwrap());
+
+        let mut a = Ascii::new(Program::new(&assemble(SHOUT).unwrap()));
+        let s = a.interact(&b""[..], &mut Vec::new()).unwrap();
+        assert_eq!(State::NeedsInput, s);
+    }
+}
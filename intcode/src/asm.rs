@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::instruction::{Instruction, Mode, Op};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl Error for AsmError {}
+
+fn err<T>(line: usize, msg: String) -> Result<T, AsmError> {
+    Err(AsmError { line, msg })
+}
+
+// A number, a label, or a label plus or minus a number.
+#[derive(Debug)]
+enum Expr<'a> {
+    Num(i128),
+    Label(&'a str, i128),
+}
+
+enum Item<'a> {
+    Ins(Op, Vec<(Mode, Expr<'a>)>),
+    Data(Vec<Expr<'a>>),
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn expr(s: &str, line: usize) -> Result<Expr<'_>, AsmError> {
+    let s = s.trim();
+    if let Ok(x) = s.parse() {
+        return Ok(Expr::Num(x));
+    }
+    let (name, off) = match s.find(['+', '-']) {
+        Some(n) => {
+            // parsed with its sign, so `x-` can take all of i128::MIN
+            let digits = s[n + 1..].trim();
+            let off: i128 = match format!("{}{}", &s[n..=n], digits).parse() {
+                Ok(x) => x,
+                Err(_) if digits.parse::<i128>().is_ok() => {
+                    return err(line, format!("offset out of range in `{}`", s))
+                }
+                Err(_) => return err(line, format!("bad offset in `{}`", s)),
+            };
+            (s[..n].trim(), off)
+        }
+        None => (s, 0),
+    };
+    if !is_label(name) {
+        return err(line, format!("expected a number or label, got `{}`", s));
+    }
+    Ok(Expr::Label(name, off))
+}
+
+fn param(s: &str, line: usize) -> Result<(Mode, Expr<'_>), AsmError> {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix('#') {
+        return Ok((Mode::Immediate, expr(rest, line)?));
+    }
+    if s.starts_with('[') && s.ends_with(']') {
+        let inner = s[1..s.len() - 1].trim();
+        if inner == "rb" {
+            return Ok((Mode::Relative, Expr::Num(0)));
+        }
+        if let Some(rest) = inner.strip_prefix("rb") {
+            let rest = rest.trim_start();
+            if let Some(off) = rest.strip_prefix('+') {
+                return Ok((Mode::Relative, expr(off, line)?));
+            }
+            if let Some(off) = rest.strip_prefix('-') {
+                let off = off.trim();
+                if let Ok(x) = format!("-{}", off).parse() {
+                    return Ok((Mode::Relative, Expr::Num(x)));
+                }
+                return match expr(off, line)? {
+                    Expr::Num(_) => err(line, format!("offset out of range in `{}`", s)),
+                    _ => err(line, format!("can't negate a label in `{}`", s)),
+                };
+            }
+        }
+        return Ok((Mode::Position, expr(inner, line)?));
+    }
+    err(
+        line,
+        format!("operand `{}` needs a mode: `#x`, `[x]` or `[rb+x]`", s),
+    )
+}
+
+fn split(s: &str) -> Vec<&str> {
+    if s.trim().is_empty() {
+        Vec::new()
+    } else {
+        s.split(',').collect()
+    }
+}
+
+fn resolve(e: &Expr, labels: &HashMap<&str, i128>, line: usize) -> Result<i128, AsmError> {
+    match e {
+        Expr::Num(x) => Ok(*x),
+        Expr::Label(name, off) => match labels.get(name) {
+            Some(a) => match a.checked_add(*off) {
+                Some(a) => Ok(a),
+                None => err(line, format!("`{}` plus {} is out of range", name, off)),
+            },
+            None => err(line, format!("undefined label `{}`", name)),
+        },
+    }
+}
+
+/// Assembles mnemonic source into a program `Program::new` can load.
+///
+/// Each line holds an optional `label:`, then an instruction or a `db` /
+/// `.data` directive, then an optional `;` comment. Parameters are written
+/// `[x]` for position mode, `#x` for immediate and `[rb+x]` for relative,
+/// where `x` is a number, a label, or a label plus or minus a number.
+pub fn assemble(src: &str) -> Result<Vec<i128>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut items = Vec::new();
+    let mut addr = 0;
+
+    for (n, text) in src.lines().enumerate() {
+        let line = n + 1;
+        let mut text = match text.find(';') {
+            Some(c) => &text[..c],
+            None => text,
+        }
+        .trim();
+
+        if let Some(c) = text.find(':') {
+            let name = text[..c].trim();
+            if !is_label(name) {
+                return err(line, format!("bad label `{}`", name));
+            }
+            if labels.insert(name, addr).is_some() {
+                return err(line, format!("duplicate label `{}`", name));
+            }
+            text = text[c + 1..].trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (word, rest) = match text.find(char::is_whitespace) {
+            Some(w) => (&text[..w], &text[w..]),
+            None => (text, ""),
+        };
+        let item = if word == "db" || word == ".data" {
+            let values = split(rest)
+                .into_iter()
+                .map(|s| expr(s, line))
+                .collect::<Result<Vec<_>, _>>()?;
+            addr += values.len() as i128;
+            Item::Data(values)
+        } else {
+            let op = match Op::from_name(word) {
+                Some(op) => op,
+                None => return err(line, format!("unknown mnemonic `{}`", word)),
+            };
+            let params = split(rest)
+                .into_iter()
+                .map(|s| param(s, line))
+                .collect::<Result<Vec<_>, _>>()?;
+            if params.len() != op.params() {
+                return err(
+                    line,
+                    format!(
+                        "`{}` takes {} operands, got {}",
+                        word,
+                        op.params(),
+                        params.len()
+                    ),
+                );
+            }
+            if let Some(d) = op.dest() {
+                if params[d].0 == Mode::Immediate {
+                    return err(line, format!("`{}` can't write to an immediate", word));
+                }
+            }
+            addr += 1 + op.params() as i128;
+            Item::Ins(op, params)
+        };
+        items.push((line, item));
+    }
+
+    let mut out = Vec::new();
+    for (line, item) in items {
+        match item {
+            Item::Ins(op, params) => {
+                let mut ins = Instruction {
+                    op,
+                    modes: [Mode::Position; 3],
+                    operands: [0; 3],
+                };
+                for (n, (m, e)) in params.iter().enumerate() {
+                    ins.modes[n] = *m;
+                    ins.operands[n] = resolve(e, &labels, line)?;
+                }
+                out.extend(ins.encode());
+            }
+            Item::Data(values) => {
+                for e in values {
+                    out.push(resolve(&e, &labels, line)?);
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_loop() {
+        let src = "
+            start: arb #1
+                   out [rb-1]
+                   add [count], #1, [count]
+                   eq [count], #16, [done]   ; stop after 16 outputs
+                   jf [done], #start
+                   hlt
+            count: db 0
+            done:  db 0
+        ";
+        let t = assemble(src).unwrap();
+
+        assert_eq!(
+            vec![109, 1, 204, -1, 1001, 16, 1, 16, 1008, 16, 16, 17, 1006, 17, 0, 99, 0, 0],
+            t
+        );
+    }
+
+    #[test]
+    fn test_amplifier() {
+        // day7's first example
+        let src = "
+            in [a]
+            in [b]
+            mul [b], #10, [b]
+            add [b], [a], [a]
+            out [a]
+            hlt
+        a:  .data 0
+        b:  .data 0
+        ";
+
+        assert_eq!(
+            vec![3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0],
+            assemble(src).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_labels_and_offsets() {
+        let src = "jt #1, #end+1\nend: db end, end-1, 7\n";
+
+        assert_eq!(vec![1105, 1, 4, 3, 2, 7], assemble(src).unwrap());
+
+        let src = "out [rb-3]\nout [rb - 3]\nout [rb + 3]\nout [rb]";
+        assert_eq!(
+            vec![204, -3, 204, -3, 204, 3, 204, 0],
+            assemble(src).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_min_offset() {
+        // the disassembler writes i128::MIN relative to rb as `rb-` and its
+        // magnitude, which is one more than i128::MAX
+        let ins = Instruction::decode(0, |i| [204, i128::MIN][i as usize]).unwrap();
+        assert_eq!(vec![204, i128::MIN], assemble(&ins.to_string()).unwrap());
+        assert_eq!(
+            vec![204, i128::MIN],
+            assemble(&format!("out [rb - {}]", i128::MIN.unsigned_abs())).unwrap()
+        );
+        assert_eq!(
+            vec![104, i128::MIN],
+            assemble(&format!("x: out #x-{}", i128::MIN.unsigned_abs())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let e = |src: &str| assemble(src).unwrap_err().to_string();
+
+        assert_eq!("line 2: unknown mnemonic `jmp`", e("hlt\njmp #0"));
+        assert_eq!("line 1: undefined label `nowhere`", e("jt #1, #nowhere"));
+        assert_eq!("line 3: duplicate label `a`", e("a: hlt\n\na: hlt"));
+        assert_eq!("line 1: `add` takes 3 operands, got 2", e("add #1, #2"));
+        assert_eq!("line 1: `in` can't write to an immediate", e("in #4"));
+        assert_eq!(
+            "line 1: operand `4` needs a mode: `#x`, `[x]` or `[rb+x]`",
+            e("out 4")
+        );
+        assert_eq!("line 1: can't negate a label in `[rb-x]`", e("out [rb-x]"));
+        assert_eq!(
+            "line 1: can't negate a label in `[rb - x]`",
+            e("out [rb - x]")
+        );
+
+        let min = i128::MIN;
+        assert_eq!(
+            format!("line 1: bad offset in `x-{}y`", min),
+            e(&format!("out #x-{}y\nx: hlt", min))
+        );
+        assert_eq!(
+            format!("line 2: offset out of range in `[rb--{}]`", 1u128 << 127),
+            e(&format!("hlt\nout [rb-{}]", min))
+        );
+        assert_eq!(
+            format!("line 1: offset out of range in `x-{}`", min),
+            e(&format!("out #x-{}\nx: hlt", min))
+        );
+        assert_eq!(
+            format!("line 1: `x` plus {} is out of range", i128::MAX),
+            e(&format!("out #x+{}\nx: hlt", i128::MAX))
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use std::env;
+use std::io;
+use std::process;
+
+use intcode::{Ascii, Program};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: ascii <program.txt>");
+            process::exit(2);
+        }
+    };
+    let tokens = match intcode::load(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    let mut a = Ascii::new(Program::new(&tokens));
+    let stdin = io::stdin();
+    if let Err(e) = a.interact(stdin.lock(), io::stdout()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,29 @@
+use std::env;
+use std::process;
+
+use intcode::Cfg;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: cfg <program.txt>");
+            process::exit(2);
+        }
+    };
+    let tokens = match intcode::load(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    let cfg = Cfg::new(&tokens);
+    for (a, target) in &cfg.self_modifying {
+        eprintln!("{} writes into code at {}", a, target);
+    }
+    for a in &cfg.computed {
+        eprintln!("{} jumps to a computed address", a);
+    }
+    print!("{}", cfg.dot());
+}
@@ -0,0 +1,38 @@
+// Helpers shared by the trace and profile tools.
+
+use std::process;
+use std::str::FromStr;
+
+use intcode::{Program, State};
+
+/// Parses a number given on the command line, exiting if it isn't one.
+pub fn parse<T: FromStr>(s: &str) -> T {
+    match s.trim().parse() {
+        Ok(x) => x,
+        Err(_) => {
+            eprintln!("bad number `{}`", s);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs `p` until it halts, printing its output. Running out of input or
+/// an error is reported on stderr and ends the run early; the result is
+/// whether it halted, so the caller can still save what it recorded before
+/// exiting.
+pub fn run(p: &mut Program) -> bool {
+    loop {
+        match p.run() {
+            Ok(State::Output(o)) => println!("{}", o),
+            Ok(State::Halted) => return true,
+            Ok(State::NeedsInput) => {
+                eprintln!("ran out of input at {}", p.i);
+                return false;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use intcode::{Debugger, Program};
+use std::env;
+use std::io;
+use std::process;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: debug <program.txt>");
+            process::exit(2);
+        }
+    };
+    let tokens = match intcode::load(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    let mut d = Debugger::new(Program::new(&tokens));
+    let stdin = io::stdin();
+    if let Err(e) = d.repl(stdin.lock(), io::stdout()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,20 @@
+use std::env;
+use std::process;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: disasm <program.txt>");
+            process::exit(2);
+        }
+    };
+    let tokens = match intcode::load(&path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+    print!("{}", intcode::disassemble(&tokens));
+}
@@ -0,0 +1,26 @@
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "usage: pack <program.txt> <out.icp>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let tokens = match intcode::load(&args[0]) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            process::exit(1);
+        }
+    };
+    let b = intcode::encode(&tokens);
+    if let Err(e) = fs::write(&args[1], &b) {
+        eprintln!("{}: {}", args[1], e);
+        process::exit(1);
+    }
+    println!("{} values in {} bytes", tokens.len(), b.len());
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::process;
+
+use common::{parse, run};
+use intcode::Program;
+
+const USAGE: &str = "usage: profile [--folded <out.folded>] <program.txt> [input...]";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let folded = match args.first().map(|s| s.as_str()) {
+        Some("--folded") if args.len() > 1 => {
+            let path = args.remove(1);
+            args.remove(0);
+            Some(path)
+        }
+        Some("--folded") => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+        _ => None,
+    };
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let tokens = match intcode::load(path) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let mut p = Program::new(&tokens);
+    p.inputs.extend(args[1..].iter().map(|s| parse::<i128>(s)));
+    p.start_profile();
+    let halted = run(&mut p);
+
+    let profile = p.profile.unwrap();
+    println!("\n{}", profile);
+    if let Some(out) = folded {
+        if let Err(e) = fs::write(&out, profile.folded() + "\n") {
+            eprintln!("{}: {}", out, e);
+            process::exit(1);
+        }
+    }
+    if !halted {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{parse, run};
+use intcode::{diverge, Program, Trace};
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+
+const USAGE: &str = "usage:
+    trace record <program.txt> <out.trace> [input...]
+    trace state <file.trace> <step>
+    trace diff <a.trace> <b.trace>";
+
+fn load(path: &str) -> Trace {
+    let t = File::open(path).and_then(|f| Trace::read(&mut BufReader::new(f)));
+    match t {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn record(program: &str, out: &str, inputs: &[String]) {
+    let tokens = match intcode::load(program) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", program, e);
+            process::exit(1);
+        }
+    };
+    let mut p = Program::new(&tokens);
+    p.inputs.extend(inputs.iter().map(|s| parse::<i128>(s)));
+    p.start_trace();
+    let halted = run(&mut p);
+
+    let t = p.trace.unwrap();
+    let written = File::create(out).and_then(|f| t.write(&mut BufWriter::new(f)));
+    if let Err(e) = written {
+        eprintln!("{}: {}", out, e);
+        process::exit(1);
+    }
+    println!("{} instructions", t.events.len());
+    if !halted {
+        process::exit(1);
+    }
+}
+
+fn state(path: &str, step: usize) {
+    let t = load(path);
+    let p = t.replay(step);
+    println!("ip = {}, rb = {}", p.i, p.rb);
+    println!("outputs = {:?}", t.outputs(step));
+    println!("inputs left = {:?}", p.inputs);
+    if let Some(e) = t.events.get(step) {
+        println!("next = {:?}", e);
+    }
+}
+
+fn diff(a: &str, b: &str) {
+    let (a, b) = (load(a), load(b));
+    match diverge(&a, &b) {
+        None => println!("identical"),
+        Some(n) => {
+            println!("first divergence at step {}", n);
+            println!("a: {:?}", a.events.get(n));
+            println!("b: {:?}", b.events.get(n));
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["record", program, out, ..] => record(program, out, &args[3..]),
+        ["state", path, step] => state(path, parse(step)),
+        ["diff", a, b] => diff(a, b),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use petgraph::dot::Dot;
+use petgraph::graph::{Graph, NodeIndex};
+
+use crate::disasm::return_address;
+use crate::instruction::{Instruction, Mode, Op};
+use crate::reachable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Fall,
+    Jump,
+    Call,
+    Return,
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Edge::Fall => "",
+            Edge::Jump => "jump",
+            Edge::Call => "call",
+            Edge::Return => "return",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A run of instructions only ever entered at the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub code: Vec<(usize, Instruction)>,
+}
+
+impl Block {
+    pub fn start(&self) -> usize {
+        self.code[0].0
+    }
+
+    pub fn last(&self) -> (usize, Instruction) {
+        *self.code.last().unwrap()
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = self
+            .code
+            .iter()
+            .map(|(a, ins)| format!("{:>6}  {}", a, ins))
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// The control-flow graph of everything `reachable` finds.
+///
+/// Subroutine calls (see `reachable`) get a `Call` edge to the callee, and
+/// every computed jump through `[rb+0]` gets a `Return` edge to every
+/// return address. Other computed jumps can't be followed and are listed in
+/// `computed`.
+pub struct Cfg {
+    pub graph: Graph<Block, Edge>,
+    pub blocks: BTreeMap<usize, NodeIndex>,
+    pub computed: Vec<usize>,
+    /// Instructions that write into code, with the address written. Only
+    /// position-mode writes can be spotted this way: `rb` isn't tracked, so
+    /// a relative-mode write into code is missed.
+    pub self_modifying: Vec<(usize, i128)>,
+}
+
+fn is_return(ins: &Instruction) -> bool {
+    ins.target().is_none()
+        && matches!(ins.op, Op::Jt | Op::Jf)
+        && ins.modes[1] == Mode::Relative
+        && ins.operands[1] == 0
+}
+
+impl Cfg {
+    pub fn new(t: &[i128]) -> Cfg {
+        let code = reachable(t);
+
+        let mut calls = BTreeMap::new();
+        let mut leaders: BTreeSet<usize> = BTreeSet::new();
+        leaders.insert(0);
+        for (a, ins) in &code {
+            // targets past a usize can't be in the program
+            if let Some(Ok(target)) = ins.target().map(usize::try_from) {
+                leaders.insert(target);
+            }
+            if let Some(ret) = return_address(t, *a, ins) {
+                leaders.insert(ret);
+                // the jump that follows is the call
+                calls.insert(a + ins.size(), ret);
+            }
+            if matches!(ins.op, Op::Jt | Op::Jf | Op::Hlt) {
+                leaders.insert(a + ins.size());
+            }
+        }
+
+        let mut graph = Graph::new();
+        let mut blocks = BTreeMap::new();
+        let mut block: Vec<(usize, Instruction)> = Vec::new();
+        for (a, ins) in &code {
+            let contiguous = match block.last() {
+                Some((b, prev)) => b + prev.size() == *a,
+                None => true,
+            };
+            if !block.is_empty() && (leaders.contains(a) || !contiguous) {
+                let b = Block {
+                    code: block.split_off(0),
+                };
+                blocks.insert(b.start(), graph.add_node(b));
+            }
+            block.push((*a, *ins));
+        }
+        if !block.is_empty() {
+            let b = Block { code: block };
+            blocks.insert(b.start(), graph.add_node(b));
+        }
+
+        let returns: BTreeSet<usize> = calls.values().copied().collect();
+        let mut edges = Vec::new();
+        let mut computed = Vec::new();
+        for n in blocks.values() {
+            let (a, ins) = graph[*n].last();
+            let next = a + ins.size();
+            if let Some(target) = ins.target() {
+                let kind = if calls.contains_key(&a) {
+                    Edge::Call
+                } else {
+                    Edge::Jump
+                };
+                if let Ok(target) = usize::try_from(target) {
+                    edges.push((*n, target, kind));
+                }
+            } else if is_return(&ins) {
+                for r in &returns {
+                    edges.push((*n, *r, Edge::Return));
+                }
+            } else if matches!(ins.op, Op::Jt | Op::Jf) {
+                computed.push(a);
+            }
+            if ins.falls_through() {
+                edges.push((*n, next, Edge::Fall));
+            }
+        }
+        for (from, to, kind) in edges {
+            if let Some(to) = blocks.get(&to) {
+                graph.add_edge(from, *to, kind);
+            }
+        }
+
+        let cells: BTreeSet<i128> = code
+            .iter()
+            .flat_map(|(a, ins)| (*a..a + ins.size()).map(|c| c as i128))
+            .collect();
+        let self_modifying = code
+            .iter()
+            .filter_map(|(a, ins)| {
+                let d = ins.op.dest()?;
+                if ins.modes[d] == Mode::Position && cells.contains(&ins.operands[d]) {
+                    Some((*a, ins.operands[d]))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Cfg {
+            graph,
+            blocks,
+            computed,
+            self_modifying,
+        }
+    }
+
+    /// The graph in Graphviz format, one node per block.
+    pub fn dot(&self) -> String {
+        format!("{}", Dot::new(&self.graph))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    const SRC: &str = "
+            arb #100
+            add #back, #0, [rb]
+            jt #1, #sub
+    back:   out [x]
+            hlt
+    sub:    add [x], #1, [x]
+            add [x], #0, [patch+1]
+    patch:  out #0
+            jt #1, [rb]
+    x:      db 0
+    ";
+
+    fn edges(cfg: &Cfg) -> Vec<(usize, usize, Edge)> {
+        let mut edges: Vec<_> = cfg
+            .graph
+            .raw_edges()
+            .iter()
+            .map(|e| {
+                (
+                    cfg.graph[e.source()].start(),
+                    cfg.graph[e.target()].start(),
+                    e.weight,
+                )
+            })
+            .collect();
+        edges.sort_by_key(|e| (e.0, e.1));
+        edges
+    }
+
+    #[test]
+    fn test_blocks() {
+        let cfg = Cfg::new(&assemble(SRC).unwrap());
+
+        assert_eq!(
+            vec![0, 9, 12],
+            cfg.blocks.keys().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(0, 12, Edge::Call), (12, 9, Edge::Return)],
+            edges(&cfg)
+        );
+        assert!(cfg.computed.is_empty());
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        let cfg = Cfg::new(&assemble(SRC).unwrap());
+
+        assert_eq!(vec![(16, 21)], cfg.self_modifying);
+    }
+
+    #[test]
+    fn test_computed_jump() {
+        // day5's jump test in position mode: the target comes from memory
+        let t = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+        let cfg = Cfg::new(&t);
+
+        assert_eq!(vec![2], cfg.computed);
+        assert!(cfg.dot().starts_with("digraph {"));
+    }
+
+    #[test]
+    fn test_far_target() {
+        // a jump to 2^64 isn't a jump back to 0
+        let cfg = Cfg::new(&[1105, 1, 1 << 64, 99]);
+
+        assert_eq!(vec![0], cfg.blocks.keys().copied().collect::<Vec<_>>());
+        assert!(edges(&cfg).is_empty());
+    }
+}
@@ -0,0 +1,536 @@
+use std::convert::TryFrom;
+
+use crate::instruction::{Instruction, Mode, Op};
+use crate::{reachable, IntcodeError, Limits, Program, State};
+
+// Programs are compiled from their first LIMIT cells; anything beyond
+// is left to the interpreter.
+const LIMIT: i128 = 1 << 20;
+const MAX_SIZE: usize = 4;
+
+type Exec = Box<dyn Fn(&mut Program) -> Result<Flow, IntcodeError> + Send + Sync>;
+
+enum Flow {
+    Continue,
+    Wrote(i128),
+    Stop(State),
+}
+
+fn load(p: &Program, a: i128) -> Result<i128, IntcodeError> {
+    if a < 0 {
+        return Err(IntcodeError::NegativeAddress { ip: p.i, addr: a });
+    }
+    Ok(p.get(a))
+}
+
+// The relative base plus `x`, failing like the interpreter on overflow.
+fn offset(p: &Program, x: i128) -> Result<i128, IntcodeError> {
+    match p.rb.checked_add(x) {
+        Some(a) => Ok(a),
+        None => Err(IntcodeError::Overflow { ip: p.i }),
+    }
+}
+
+// Each mode is a type, so the closure for an instruction is built with its
+// modes baked in and reads its operands without any further calls through
+// a pointer.
+trait Read {
+    fn read(p: &Program, x: i128) -> Result<i128, IntcodeError>;
+}
+
+trait Write {
+    fn addr(p: &Program, x: i128) -> Result<i128, IntcodeError>;
+}
+
+struct Pos;
+struct Imm;
+struct Rel;
+
+impl Read for Pos {
+    #[inline(always)]
+    fn read(p: &Program, x: i128) -> Result<i128, IntcodeError> {
+        load(p, x)
+    }
+}
+
+impl Read for Imm {
+    #[inline(always)]
+    fn read(_: &Program, x: i128) -> Result<i128, IntcodeError> {
+        Ok(x)
+    }
+}
+
+impl Read for Rel {
+    #[inline(always)]
+    fn read(p: &Program, x: i128) -> Result<i128, IntcodeError> {
+        load(p, offset(p, x)?)
+    }
+}
+
+fn positive(p: &Program, a: i128) -> Result<i128, IntcodeError> {
+    if a < 0 {
+        return Err(IntcodeError::NegativeAddress { ip: p.i, addr: a });
+    }
+    Ok(a)
+}
+
+impl Write for Pos {
+    #[inline(always)]
+    fn addr(p: &Program, x: i128) -> Result<i128, IntcodeError> {
+        positive(p, x)
+    }
+}
+
+impl Write for Rel {
+    #[inline(always)]
+    fn addr(p: &Program, x: i128) -> Result<i128, IntcodeError> {
+        positive(p, offset(p, x)?)
+    }
+}
+
+// Runs `$body` with `$t` standing for the type of mode `$m`. Decoding has
+// already turned away immediate-mode writes.
+macro_rules! read_mode {
+    ($m:expr, $t:ident => $body:expr) => {
+        match $m {
+            Mode::Position => {
+                type $t = Pos;
+                $body
+            }
+            Mode::Immediate => {
+                type $t = Imm;
+                $body
+            }
+            Mode::Relative => {
+                type $t = Rel;
+                $body
+            }
+        }
+    };
+}
+
+macro_rules! write_mode {
+    ($m:expr, $t:ident => $body:expr) => {
+        match $m {
+            Mode::Relative => {
+                type $t = Rel;
+                $body
+            }
+            _ => {
+                type $t = Pos;
+                $body
+            }
+        }
+    };
+}
+
+// add, mul, lt and eq: two reads, an operation and a write.
+fn arith<A: Read, B: Read, C: Write, F>(x: i128, y: i128, d: i128, next: i128, f: F) -> Exec
+where
+    F: Fn(i128, i128) -> i128 + Send + Sync + 'static,
+{
+    Box::new(move |p| {
+        let v = f(A::read(p, x)?, B::read(p, y)?);
+        let c = C::addr(p, d)?;
+        p.set(c, v);
+        p.i = next;
+        Ok(Flow::Wrote(c))
+    })
+}
+
+fn binary<F>(ins: &Instruction, next: i128, f: F) -> Exec
+where
+    F: Fn(i128, i128) -> i128 + Send + Sync + 'static,
+{
+    let [x, y, d] = ins.operands;
+    let m = ins.modes;
+    read_mode!(m[0], A => read_mode!(m[1], B => write_mode!(m[2], C => {
+        arith::<A, B, C, F>(x, y, d, next, f)
+    })))
+}
+
+fn input<C: Write>(d: i128, next: i128) -> Exec {
+    Box::new(move |p| {
+        let c = C::addr(p, d)?;
+        match p.inputs.pop_front() {
+            Some(v) => {
+                p.set(c, v);
+                p.i = next;
+                Ok(Flow::Wrote(c))
+            }
+            None => Ok(Flow::Stop(State::NeedsInput)),
+        }
+    })
+}
+
+fn output<A: Read>(x: i128, next: i128) -> Exec {
+    Box::new(move |p| {
+        let o = A::read(p, x)?;
+        p.i = next;
+        Ok(Flow::Stop(State::Output(o)))
+    })
+}
+
+fn jump<A: Read, B: Read>(x: i128, y: i128, next: i128, jt: bool) -> Exec {
+    Box::new(move |p| {
+        let t = A::read(p, x)?;
+        let target = B::read(p, y)?;
+        p.i = if (t != 0) == jt { target } else { next };
+        Ok(Flow::Continue)
+    })
+}
+
+fn arb<A: Read>(x: i128, next: i128) -> Exec {
+    Box::new(move |p| {
+        let v = A::read(p, x)?;
+        p.rb = offset(p, v)?;
+        p.i = next;
+        Ok(Flow::Continue)
+    })
+}
+
+fn compile(a: usize, ins: &Instruction) -> Exec {
+    let next = (a + ins.size()) as i128;
+    let [x, y, _] = ins.operands;
+    let m = ins.modes;
+
+    match ins.op {
+        Op::Add => binary(ins, next, i128::wrapping_add),
+        Op::Mul => binary(ins, next, i128::wrapping_mul),
+        Op::Lt => binary(ins, next, |a, b| (a < b) as i128),
+        Op::Eq => binary(ins, next, |a, b| (a == b) as i128),
+        Op::In => write_mode!(m[0], C => input::<C>(x, next)),
+        Op::Out => read_mode!(m[0], A => output::<A>(x, next)),
+        Op::Jt | Op::Jf => {
+            let jt = ins.op == Op::Jt;
+            read_mode!(m[0], A => read_mode!(m[1], B => jump::<A, B>(x, y, next, jt)))
+        }
+        Op::Arb => read_mode!(m[0], A => arb::<A>(x, next)),
+        Op::Hlt => Box::new(|_| Ok(Flow::Stop(State::Halted))),
+    }
+}
+
+/// A program translated ahead of time into a closure per instruction, with
+/// operand modes and jump targets resolved up front.
+///
+/// Code `reachable` finds is compiled up front, and anything else in the
+/// program's first cells the first time it runs, such as code only reached
+/// through a computed jump. When a write lands inside a compiled
+/// instruction, that instruction is dropped and compiled again from its new
+/// contents if it runs again, so self-modifying programs still behave.
+/// Instructions outside the program, or that don't decode, are left to the
+/// interpreter.
+///
+/// The program is owned so that every write, the program's own or one made
+/// between runs with `set`, drops the code compiled from the cells it
+/// changes.
+pub struct Compiled {
+    program: Program,
+    code: Vec<Option<Exec>>,
+    // The size of the compiled instruction starting at each cell, or 0.
+    // Instructions reached from different places can overlap.
+    sizes: Vec<usize>,
+}
+
+impl Compiled {
+    /// Compiles `p` as its memory stands now, so patches like day13's
+    /// quarters should be applied first.
+    pub fn new(p: Program) -> Compiled {
+        let len = p
+            .cells()
+            .iter()
+            .map(|c| c.0 + 1)
+            .filter(|a| *a <= LIMIT)
+            .max()
+            .unwrap_or(0);
+        let t: Vec<i128> = (0..len).map(|a| p.get(a)).collect();
+
+        let mut code: Vec<Option<Exec>> = (0..t.len()).map(|_| None).collect();
+        let mut sizes = vec![0; t.len()];
+        for (a, ins) in reachable(&t) {
+            code[a] = Some(compile(a, &ins));
+            sizes[a] = ins.size();
+        }
+        Compiled {
+            program: p,
+            code,
+            sizes,
+        }
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn into_program(self) -> Program {
+        self.program
+    }
+
+    /// Queues an input for the program.
+    pub fn input(&mut self, x: i128) {
+        self.program.inputs.push_back(x);
+    }
+
+    /// Writes to the program's memory, dropping any compiled instruction
+    /// the write lands in.
+    pub fn set(&mut self, a: i128, x: i128) {
+        self.program.set(a, x);
+        self.invalidate(a);
+    }
+
+    /// How many instructions are still compiled.
+    pub fn size(&self) -> usize {
+        self.code.iter().filter(|c| c.is_some()).count()
+    }
+
+    fn invalidate(&mut self, a: i128) {
+        // an instruction at the end can have operands past the last cell
+        let a = match usize::try_from(a) {
+            Ok(a) if a < self.sizes.len() + MAX_SIZE => a,
+            _ => return,
+        };
+        for back in 0..MAX_SIZE.min(a + 1) {
+            if self.sizes.get(a - back).is_some_and(|s| *s > back) {
+                self.code[a - back] = None;
+                self.sizes[a - back] = 0;
+            }
+        }
+    }
+
+    // Compiles the instruction at `ip` if it's inside the compiled cells and
+    // decodes.
+    fn compile_at(&mut self, ip: i128) -> bool {
+        let a = match usize::try_from(ip) {
+            Ok(a) if a < self.code.len() => a,
+            _ => return false,
+        };
+        let p = &self.program;
+        match Instruction::decode(ip, |i| p.get(i)) {
+            Ok(ins) => {
+                self.code[a] = Some(compile(a, &ins));
+                self.sizes[a] = ins.size();
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn interpret(&mut self) -> Result<Option<State>, IntcodeError> {
+        let p = &mut self.program;
+        let write = Instruction::decode(p.i, |i| p.get(i)).ok().and_then(|ins| {
+            let d = ins.op.dest()?;
+            match ins.modes[d] {
+                Mode::Relative => p.rb.checked_add(ins.operands[d]),
+                _ => Some(ins.operands[d]),
+            }
+        });
+        let s = p.step()?;
+        if let Some(a) = write {
+            self.invalidate(a);
+        }
+        Ok(s)
+    }
+
+    /// Runs the program like `Program::run`. Traced, profiled or checked
+    /// programs are handed straight to the interpreter.
+    pub fn run(&mut self) -> Result<State, IntcodeError> {
+        let p = &self.program;
+        // only the instruction limit is checked here
+        let limits = Limits {
+            instructions: p.limits.instructions,
+            ..Limits::default()
+        };
+        if p.trace.is_some() || p.profile.is_some() || p.checked || p.limits != limits {
+            return self.program.run();
+        }
+        let limit = limits.instructions.unwrap_or(usize::MAX);
+        loop {
+            let ip = self.program.i;
+            let f = match usize::try_from(ip) {
+                Ok(a) => self.code.get(a).and_then(|f| f.as_ref()),
+                Err(_) => None,
+            };
+            let f = match f {
+                Some(f) => f,
+                None => {
+                    if !self.compile_at(ip) {
+                        if let Some(s) = self.interpret()? {
+                            return Ok(s);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let p = &mut self.program;
+            if p.count >= limit {
+                return Err(IntcodeError::InstructionLimitExceeded {
+                    limit,
+                    state: Box::new(p.vm_state()),
+                });
+            }
+            match f(p)? {
+                Flow::Continue => p.count += 1,
+                Flow::Wrote(a) => {
+                    p.count += 1;
+                    self.invalidate(a);
+                }
+                Flow::Stop(s) => {
+                    if let State::Output(_) = s {
+                        p.count += 1;
+                        p.outputs += 1;
+                    }
+                    return Ok(s);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{DAY5, QUINE};
+
+    fn outputs(t: &[i128], inputs: &[i128], compiled: bool) -> (Vec<i128>, Program) {
+        let mut p = Program::new(t);
+        p.inputs.extend(inputs);
+        let mut c = Compiled::new(p.clone());
+        let mut out = Vec::new();
+        loop {
+            let s = if compiled { c.run() } else { p.run() };
+            match s.unwrap() {
+                State::Output(o) => out.push(o),
+                State::Halted if compiled => return (out, c.into_program()),
+                State::Halted => return (out, p),
+                State::NeedsInput => panic!("starved"),
+            }
+        }
+    }
+
+    fn same(t: &[i128], inputs: &[i128]) -> Vec<i128> {
+        let (a, p) = outputs(t, inputs, false);
+        let (b, q) = outputs(t, inputs, true);
+        assert_eq!(a, b);
+        assert_eq!(p.cells(), q.cells());
+        assert_eq!((p.i, p.rb, p.count), (q.i, q.rb, q.count));
+        a
+    }
+
+    #[test]
+    fn test_day5() {
+        assert_eq!(vec![999], same(&DAY5, &[7]));
+        assert_eq!(vec![1000], same(&DAY5, &[8]));
+        assert_eq!(vec![1001], same(&DAY5, &[9]));
+    }
+
+    #[test]
+    fn test_quine() {
+        assert_eq!(QUINE.to_vec(), same(&QUINE, &[]));
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // patches the `out #0` at 8 to output 7 instead
+        let t = [1101, 7, 0, 9, 1105, 1, 8, 99, 104, 0, 99];
+        let mut c = Compiled::new(Program::new(&t));
+        let compiled = c.size();
+
+        // the write drops the `out`, which is compiled again when it runs
+        assert_eq!(Ok(State::Output(7)), c.run());
+        assert_eq!(compiled, c.size());
+        assert_eq!(vec![7], same(&t, &[]));
+    }
+
+    #[test]
+    fn test_computed_jump() {
+        // `reachable` can't follow the jump through cell 7 to the `out` at 4,
+        // so it's compiled when it first runs
+        let mut c = Compiled::new(Program::new(&[105, 1, 7, 99, 104, 5, 99, 4]));
+        assert_eq!(1, c.size());
+        assert_eq!(Ok(State::Output(5)), c.run());
+        assert_eq!(2, c.size());
+    }
+
+    #[test]
+    fn test_far_jump() {
+        // a jump to 2^64 + 3 mustn't land on what was compiled for 3, nor
+        // one to 2^64 on 0
+        let far = 1 << 64;
+        for target in [far + 3, far] {
+            let t = [1105, 1, target, 104, 7, 99];
+            let mut p = Program::new(&t);
+            p.limits.instructions = Some(100);
+            let mut c = Compiled::new(p.clone());
+            assert_eq!(p.run(), c.run());
+        }
+    }
+
+    #[test]
+    fn test_overlapping() {
+        // the `jt` at 4 and the `add` at 6 both cover cell 6, and the write
+        // to it has to drop both
+        let t = [20001, 4, 0, 6, 1105, 1, 1];
+        let mut c = Compiled::new(Program::new(&t));
+        assert_eq!(Program::new(&t).run(), c.run());
+    }
+
+    #[test]
+    fn test_write_past_end() {
+        // the `out` operand is past the end of the program until the add
+        // writes it
+        let mut c = Compiled::new(Program::new(&[1101, 7, 0, 5, 104]));
+
+        assert_eq!(Ok(State::Output(7)), c.run());
+    }
+
+    #[test]
+    fn test_set_between_runs() {
+        // `in a; out a; jt 1, 0`, patched to output 5 more than it reads
+        let mut c = Compiled::new(Program::new(&[3, 9, 4, 9, 1105, 1, 0, 99, 99, 0]));
+        c.input(1);
+        assert_eq!(Ok(State::Output(1)), c.run());
+        assert_eq!(Ok(State::NeedsInput), c.run());
+
+        let compiled = c.size();
+        c.set(2, 1001);
+        c.set(3, 9);
+        c.set(4, 5);
+        c.set(5, 9);
+        c.set(6, 4);
+        c.set(7, 9);
+        assert!(c.size() < compiled);
+        c.input(2);
+        assert_eq!(Ok(State::Output(7)), c.run());
+        assert_eq!(7, c.program().get(9));
+    }
+
+    #[test]
+    fn test_errors_and_limit() {
+        let mut c = Compiled::new(Program::new(&[1, -1, 0, 0, 99]));
+        assert_eq!(
+            Err(IntcodeError::NegativeAddress { ip: 0, addr: -1 }),
+            c.run()
+        );
+
+        // the relative base overflows on the second `arb`, or when a
+        // parameter is read relative to it
+        for t in [[109, i128::MAX, 109, 1, 99], [109, i128::MAX, 204, 1, 99]] {
+            let mut c = Compiled::new(Program::new(&t));
+            assert_eq!(Err(IntcodeError::Overflow { ip: 2 }), c.run());
+        }
+
+        let mut p = Program::new(&[1105, 1, 0]);
+        p.limits.instructions = Some(10);
+        let mut c = Compiled::new(p);
+        let e = c.run();
+        assert_eq!(10, c.program().count);
+        assert_eq!(
+            Err(IntcodeError::InstructionLimitExceeded {
+                limit: 10,
+                state: Box::new(c.program().vm_state())
+            }),
+            e
+        );
+    }
+}
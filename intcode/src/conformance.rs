@@ -0,0 +1,392 @@
+// Behaviour every interpreter has to agree on. Each case runs through both
+// `Program` and `Compiled`, which have to match each other as well as the
+// expected result, and the fuzzer at the bottom checks they keep matching on
+// programs nobody wrote by hand.
+
+use num_bigint::BigInt;
+use proptest::prelude::*;
+
+use crate::fixtures::{DAY5, QUINE};
+use crate::{Compiled, IntcodeError, Program, State};
+
+// Where the operands of the single instruction under test point. The
+// relative base is set to RB first, and relative operands point 10 cells
+// past their position-mode counterparts so a mix-up shows.
+const RB: i128 = 50;
+const A: i128 = 30;
+const B: i128 = 31;
+const C: i128 = 32;
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    result: Result<State, IntcodeError>,
+    outputs: Vec<i128>,
+    memory: Vec<(i128, i128)>,
+    registers: (i128, i128, usize),
+}
+
+fn execute(t: &[i128], inputs: &[i128], compiled: bool) -> Outcome {
+    let mut p = Program::new(t);
+    p.inputs.extend(inputs);
+    p.limits.instructions = Some(10_000);
+    let mut c = Compiled::new(p.clone());
+    let mut outputs = Vec::new();
+    let result = loop {
+        let s = if compiled { c.run() } else { p.run() };
+        match s {
+            Ok(State::Output(o)) => outputs.push(o),
+            s => break s,
+        }
+    };
+    if compiled {
+        p = c.into_program();
+    }
+    Outcome {
+        result,
+        outputs,
+        memory: p.cells(),
+        registers: (p.i, p.rb, p.count),
+    }
+}
+
+// Runs `t` both ways, checks they agree and returns what happened.
+fn conform(t: &[i128], inputs: &[i128]) -> Outcome {
+    let interpreted = execute(t, inputs, false);
+    assert_eq!(interpreted, execute(t, inputs, true), "{:?}", t);
+    interpreted
+}
+
+fn outputs(t: &[i128], inputs: &[i128]) -> Vec<i128> {
+    let o = conform(t, inputs);
+    assert_eq!(Ok(State::Halted), o.result, "{:?}", t);
+    o.outputs
+}
+
+// The operand that makes a parameter in mode `m` read `x`, placing `x` in
+// memory at `at`, or 10 past it for relative mode.
+fn operand(t: &mut [i128], m: i128, at: i128, x: i128) -> i128 {
+    match m {
+        0 => {
+            t[at as usize] = x;
+            at
+        }
+        1 => x,
+        _ => {
+            t[(at + 10) as usize] = x;
+            at + 10 - RB
+        }
+    }
+}
+
+// `arb #RB`, then one instruction with `params`, then `hlt`, with room for
+// the data it points at.
+fn single(op: i128, modes: &[i128], params: &[(i128, i128)]) -> Vec<i128> {
+    let mut t = vec![0; 64];
+    t[0] = 109;
+    t[1] = RB;
+    let mut code = op;
+    let mut div = 100;
+    for (n, (m, (at, x))) in modes.iter().zip(params).enumerate() {
+        code += m * div;
+        div *= 10;
+        t[3 + n] = operand(&mut t, *m, *at, *x);
+    }
+    t[2] = code;
+    t[3 + params.len()] = 99;
+    t
+}
+
+fn cell(o: &Outcome, a: i128) -> i128 {
+    o.memory.iter().find(|c| c.0 == a).map_or(0, |c| c.1)
+}
+
+// The cell a write in mode `m` lands on.
+fn dest(m: i128) -> i128 {
+    match m {
+        0 => C,
+        _ => C + 10,
+    }
+}
+
+#[test]
+fn test_arithmetic_and_compare() {
+    type Expected = fn(i128, i128) -> i128;
+    let ops: [(i128, Expected); 4] = [
+        (1, |a, b| a + b),
+        (2, |a, b| a * b),
+        (7, |a, b| (a < b) as i128),
+        (8, |a, b| (a == b) as i128),
+    ];
+    for (op, f) in ops.iter() {
+        for (a, b) in &[(7, 5), (5, 7), (6, 6), (-3, 4)] {
+            for ma in 0..3 {
+                for mb in 0..3 {
+                    for mc in &[0, 2] {
+                        let t = single(*op, &[ma, mb, *mc], &[(A, *a), (B, *b), (C, 0)]);
+                        let o = conform(&t, &[]);
+
+                        assert_eq!(Ok(State::Halted), o.result, "{:?}", t);
+                        assert_eq!(f(*a, *b), cell(&o, dest(*mc)), "{:?}", t);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_immediate_writes() {
+    for op in &[1, 2, 7, 8] {
+        let t = single(*op, &[0, 0, 1], &[(A, 1), (B, 2), (C, 0)]);
+        assert_eq!(
+            Err(IntcodeError::BadMode { ip: 2, mode: 1 }),
+            conform(&t, &[]).result
+        );
+    }
+    assert_eq!(
+        Err(IntcodeError::BadMode { ip: 0, mode: 1 }),
+        conform(&[103, 5, 99], &[1]).result
+    );
+}
+
+#[test]
+fn test_input() {
+    for m in &[0, 2] {
+        let t = single(3, &[*m], &[(C, 0)]);
+        let o = conform(&t, &[-17]);
+
+        assert_eq!(Ok(State::Halted), o.result);
+        assert_eq!(-17, cell(&o, dest(*m)));
+        // no input queued stops before the read
+        let o = conform(&t, &[]);
+        assert_eq!((Ok(State::NeedsInput), 2), (o.result, o.registers.0));
+    }
+}
+
+#[test]
+fn test_output() {
+    for m in 0..3 {
+        let t = single(4, &[m], &[(A, 1234)]);
+        assert_eq!(vec![1234], outputs(&t, &[]));
+    }
+}
+
+#[test]
+fn test_jumps() {
+    // jt/jf to an `out #1` at 60, falling through to `out #0` otherwise
+    for (op, cond, taken) in &[(5, 3, true), (5, 0, false), (6, 0, true), (6, -2, false)] {
+        for mc in 0..3 {
+            for mt in 0..3 {
+                let mut t = single(*op, &[mc, mt], &[(A, *cond), (B, 60)]);
+                t[5..8].copy_from_slice(&[104, 0, 99]);
+                t[60..63].copy_from_slice(&[104, 1, 99]);
+                let expected = if *taken { 1 } else { 0 };
+                assert_eq!(vec![expected], outputs(&t, &[]), "{:?}", t);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_relative_base() {
+    for m in 0..3 {
+        // moves the base by 5, then outputs [rb+0]
+        let mut t = single(9, &[m], &[(A, 5)]);
+        t[4..7].copy_from_slice(&[204, 0, 99]);
+        t[(RB + 5) as usize] = 77;
+        assert_eq!(vec![77], outputs(&t, &[]), "{:?}", t);
+    }
+    // the base can go negative as long as nothing is read through it
+    assert_eq!(vec![3], outputs(&[109, -10, 204, 15, 99, 3], &[]));
+
+    // the base overflowing is an error, whether `arb` moves it past either
+    // end or a relative operand adds to it
+    let (max, min) = (i128::MAX, i128::MIN);
+    let overflow = Err(IntcodeError::Overflow { ip: 2 });
+    assert_eq!(overflow, conform(&[109, max, 109, 1, 99], &[]).result);
+    assert_eq!(overflow, conform(&[109, -1, 109, min, 99], &[]).result);
+    assert_eq!(overflow, conform(&[109, max, 204, 1, 99], &[]).result);
+    assert_eq!(
+        overflow,
+        conform(&[109, max, 21101, 1, 1, 1, 99], &[]).result
+    );
+    // and tracing doesn't change that
+    let traced: [&[i128]; 3] = [
+        &[109, max, 109, 1, 99],
+        &[109, max, 204, 1, 99],
+        &[109, max, 21101, 1, 1, 1, 99],
+    ];
+    for t in traced {
+        let mut p = Program::new(t);
+        p.start_trace();
+        assert_eq!(overflow, p.run());
+    }
+    // but the very top cell is reachable
+    assert_eq!(vec![0], outputs(&[109, max, 204, 0, 99], &[]));
+    assert_eq!(
+        vec![5],
+        outputs(&[109, max, 21101, 2, 3, 0, 204, 0, 99], &[])
+    );
+    // though an instruction there has nowhere to keep its operands
+    assert_eq!(
+        Err(IntcodeError::Overflow { ip: max }),
+        conform(&[109, max, 21101, 1, 0, 0, 1105, 1, max], &[]).result
+    );
+}
+
+#[test]
+fn test_day5() {
+    let eq8 = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+    let lt8 = [3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+    let eq8_imm = [3, 3, 1108, -1, 8, 3, 4, 3, 99];
+    let lt8_imm = [3, 3, 1107, -1, 8, 3, 4, 3, 99];
+    let jump = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+    let jump_imm = [3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+    for x in -1..=10 {
+        assert_eq!(vec![(x == 8) as i128], outputs(&eq8, &[x]));
+        assert_eq!(vec![(x < 8) as i128], outputs(&lt8, &[x]));
+        assert_eq!(vec![(x == 8) as i128], outputs(&eq8_imm, &[x]));
+        assert_eq!(vec![(x < 8) as i128], outputs(&lt8_imm, &[x]));
+        assert_eq!(vec![(x != 0) as i128], outputs(&jump, &[x]));
+        assert_eq!(vec![(x != 0) as i128], outputs(&jump_imm, &[x]));
+        assert_eq!(vec![999 + (x.cmp(&8) as i128) + 1], outputs(&DAY5, &[x]));
+    }
+    // the first day5 example: echo then modify itself to halt
+    assert_eq!(vec![42], outputs(&[3, 0, 4, 0, 99], &[42]));
+    let o = conform(&[1002, 4, 3, 4, 33], &[]);
+    assert_eq!(Ok(State::Halted), o.result);
+    assert!(o.memory.contains(&(4, 99)));
+}
+
+#[test]
+fn test_quines() {
+    assert_eq!(QUINE.to_vec(), outputs(&QUINE, &[]));
+
+    // the same loop ending on `lt` and `jt` instead
+    let lt = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1007, 100, 16, 101, 1005, 101, 0, 99,
+    ];
+    assert_eq!(lt.to_vec(), outputs(&lt, &[]));
+}
+
+#[test]
+fn test_large_numbers() {
+    assert_eq!(
+        vec![1219070632396864],
+        outputs(&[1102, 34915192, 34915192, 7, 4, 7, 99, 0], &[])
+    );
+    assert_eq!(
+        vec![1125899906842624],
+        outputs(&[104, 1125899906842624, 99], &[])
+    );
+
+    // past i64 but inside i128
+    let big = i64::MAX as i128;
+    assert_eq!(vec![big * 4], outputs(&[1102, big, 4, 7, 4, 7, 99, 0], &[]));
+
+    // past i128 only a BigInt program gets the right answer; the default
+    // wraps, and a checked program stops
+    let t = [1102, i128::MAX, 4, 7, 4, 7, 99, 0];
+    assert_eq!(vec![i128::MAX.wrapping_mul(4)], outputs(&t, &[]));
+    for compiled in [false, true] {
+        let mut p = Program::new(&t);
+        p.checked = true;
+        let s = if compiled {
+            Compiled::new(p).run()
+        } else {
+            p.run()
+        };
+        assert_eq!(Err(IntcodeError::Overflow { ip: 0 }), s);
+    }
+
+    let words: Vec<BigInt> = t.iter().map(|x| BigInt::from(*x)).collect();
+    let mut p = Program::from_words(&words);
+    assert_eq!(
+        Ok(State::Output(BigInt::from(i128::MAX) * BigInt::from(4))),
+        p.run()
+    );
+}
+
+#[test]
+fn test_bad_programs() {
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode { ip: 0, value: 42 }),
+        conform(&[42], &[]).result
+    );
+    assert_eq!(
+        Err(IntcodeError::NegativeAddress { ip: 0, addr: -1 }),
+        conform(&[4, -1, 99], &[]).result
+    );
+    assert_eq!(
+        Err(IntcodeError::NegativeAddress { ip: 2, addr: -1 }),
+        conform(&[109, -2, 204, 1, 99], &[]).result
+    );
+    // running off the end reads an unknown opcode 0
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode { ip: 4, value: 0 }),
+        conform(&[1101, 1, 1, 0], &[]).result
+    );
+}
+
+// One random instruction: an opcode, modes and operands that mostly point
+// back into the program so reads and writes hit code as well as data.
+// Mostly small enough to land in the program, now and then at the edges
+// of the word so address and relative base overflow get exercised too, or
+// just past a usize, where an address cast down would wrap back into it.
+fn any_operand() -> impl Strategy<Value = i128> {
+    let extreme = prop::sample::select(vec![
+        i128::MAX,
+        i128::MAX - 1,
+        i128::MIN,
+        i128::MIN + 1,
+        i64::MAX as i128,
+        -(i64::MAX as i128),
+    ]);
+    let far = (
+        prop::sample::select(vec![1 << 64, usize::MAX as i128]),
+        -2..12i128,
+    )
+        .prop_map(|(base, k)| base + k);
+    prop_oneof![15 => -4..48i128, 1 => extreme, 1 => far]
+}
+
+fn instruction() -> impl Strategy<Value = Vec<i128>> {
+    let op = prop::sample::select(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 99]);
+    (
+        op,
+        0..3i128,
+        0..3i128,
+        0..3i128,
+        prop::collection::vec(any_operand(), 3),
+    )
+        .prop_map(|(op, a, b, c, operands)| {
+            let size = match op {
+                1 | 2 | 7 | 8 => 4,
+                5 | 6 => 3,
+                99 => 1,
+                _ => 2,
+            };
+            let mut t = vec![op + 100 * a + 1000 * b + 10000 * c];
+            t.extend(&operands[..size - 1]);
+            t
+        })
+}
+
+fn program() -> impl Strategy<Value = Vec<i128>> {
+    (
+        prop::collection::vec(instruction(), 1..12),
+        prop::collection::vec(-10..40i128, 0..12),
+    )
+        .prop_map(|(code, data)| code.concat().into_iter().chain(data).collect())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn test_fuzz(t in program(), inputs in prop::collection::vec(-5..50i128, 0..4)) {
+        let interpreted = execute(&t, &inputs, false);
+        let compiled = execute(&t, &inputs, true);
+        prop_assert_eq!(interpreted, compiled);
+    }
+}
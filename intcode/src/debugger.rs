@@ -0,0 +1,361 @@
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{BufRead, Write};
+
+use crate::{Instruction, IntcodeError, Program, State};
+
+const HISTORY: usize = 3;
+const LOOKAHEAD: usize = 5;
+// The most cells one `x` shows.
+const MAX_CELLS: i128 = 1000;
+
+const HELP: &str = "\
+s, step [n]       execute n instructions (default 1)
+c, continue       run until a breakpoint, watchpoint, input wait or halt
+b, break <addr>   set a breakpoint
+d, delete <addr>  clear a breakpoint
+w, watch <addr>   stop when a memory cell changes
+unwatch <addr>    clear a watchpoint
+r, regs           show ip, rb and queued input
+x <addr> [n]      show n memory cells (default 1, at most 1000)
+set <reg> <val>   set ip, rb or a memory address
+l, list           disassemble around ip
+in <val>...       queue input
+out               show output so far
+q, quit           exit
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint(i128),
+    Watchpoint { addr: i128, old: i128, new: i128 },
+    NeedsInput,
+    Halted,
+}
+
+pub struct Debugger {
+    pub program: Program,
+    pub breakpoints: BTreeSet<i128>,
+    pub watchpoints: BTreeSet<i128>,
+    pub outputs: Vec<i128>,
+    history: VecDeque<i128>,
+}
+
+impl Debugger {
+    pub fn new(program: Program) -> Debugger {
+        Debugger {
+            program,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            outputs: Vec::new(),
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let ip = self.program.i;
+        let before: Vec<(i128, i128)> = self
+            .watchpoints
+            .iter()
+            .map(|a| (*a, self.program.get(*a)))
+            .collect();
+
+        let stop = match self.program.step()? {
+            Some(State::NeedsInput) => return Ok(Stop::NeedsInput),
+            Some(State::Halted) => return Ok(Stop::Halted),
+            Some(State::Output(o)) => {
+                self.outputs.push(o);
+                Stop::Step
+            }
+            None => Stop::Step,
+        };
+
+        self.history.push_back(ip);
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+
+        for (addr, old) in before {
+            let new = self.program.get(addr);
+            if new != old {
+                return Ok(Stop::Watchpoint { addr, old, new });
+            }
+        }
+        Ok(stop)
+    }
+
+    /// Steps until something other than an ordinary instruction happens.
+    /// Always executes at least one instruction, so continuing from a
+    /// breakpoint moves past it.
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        loop {
+            match self.step()? {
+                Stop::Step => {}
+                stop => return Ok(stop),
+            }
+            if self.breakpoints.contains(&self.program.i) {
+                return Ok(Stop::Breakpoint(self.program.i));
+            }
+        }
+    }
+
+    fn line(&self, a: i128) -> (String, i128) {
+        let marker = if a == self.program.i { "=>" } else { "  " };
+        let bp = if self.breakpoints.contains(&a) {
+            "*"
+        } else {
+            " "
+        };
+        match Instruction::decode(a, |i| self.program.get(i)) {
+            Ok(ins) => (
+                format!("{}{}{:>6}  {}", marker, bp, a, ins),
+                ins.size() as i128,
+            ),
+            Err(_) => (
+                format!("{}{}{:>6}  DATA {}", marker, bp, a, self.program.get(a)),
+                1,
+            ),
+        }
+    }
+
+    /// The last few instructions executed, then the one at `ip` and the ones
+    /// that follow it in memory.
+    pub fn listing(&self) -> String {
+        let mut lines: Vec<String> = self.history.iter().map(|a| self.line(*a).0).collect();
+        let mut a = self.program.i;
+        for _ in 0..LOOKAHEAD {
+            let (s, size) = self.line(a);
+            lines.push(s);
+            // nothing follows the top address
+            a = match a.checked_add(size) {
+                Some(a) => a,
+                None => break,
+            };
+        }
+        lines.join("\n")
+    }
+
+    fn describe(&self, stop: Stop) -> String {
+        match stop {
+            Stop::Step => self.line(self.program.i).0,
+            Stop::Breakpoint(a) => format!("breakpoint at {}\n{}", a, self.line(a).0),
+            Stop::Watchpoint { addr, old, new } => format!(
+                "[{}] changed from {} to {}\n{}",
+                addr,
+                old,
+                new,
+                self.line(self.program.i).0
+            ),
+            Stop::NeedsInput => format!("waiting for input\n{}", self.line(self.program.i).0),
+            Stop::Halted => "halted".to_string(),
+        }
+    }
+
+    fn command(&mut self, words: &[&str]) -> Result<String, String> {
+        let num = |n: usize| -> Result<i128, String> {
+            let w = words.get(n).ok_or("missing argument")?;
+            w.parse().map_err(|_| format!("bad number `{}`", w))
+        };
+        let run = |r: Result<Stop, IntcodeError>| r.map_err(|e| e.to_string());
+
+        match words[0] {
+            "s" | "step" => {
+                let n = if words.len() > 1 { num(1)? } else { 1 };
+                let mut stop = Stop::Step;
+                for _ in 0..n {
+                    stop = run(self.step())?;
+                    if stop != Stop::Step {
+                        break;
+                    }
+                }
+                Ok(self.describe(stop))
+            }
+            "c" | "continue" => {
+                let stop = run(self.cont())?;
+                Ok(self.describe(stop))
+            }
+            "b" | "break" => {
+                self.breakpoints.insert(num(1)?);
+                Ok(String::new())
+            }
+            "d" | "delete" => {
+                self.breakpoints.remove(&num(1)?);
+                Ok(String::new())
+            }
+            "w" | "watch" => {
+                self.watchpoints.insert(num(1)?);
+                Ok(String::new())
+            }
+            "unwatch" => {
+                self.watchpoints.remove(&num(1)?);
+                Ok(String::new())
+            }
+            "r" | "regs" => Ok(format!(
+                "ip = {}, rb = {}, inputs = {:?}",
+                self.program.i, self.program.rb, self.program.inputs
+            )),
+            "x" => {
+                let a = num(1)?;
+                let n = if words.len() > 2 { num(2)? } else { 1 };
+                let end = a
+                    .checked_add(n.min(MAX_CELLS))
+                    .ok_or("address out of range")?;
+                let cells: Vec<String> = (a..end)
+                    .map(|i| format!("[{}] = {}", i, self.program.get(i)))
+                    .collect();
+                Ok(cells.join("\n"))
+            }
+            "set" => {
+                let v = num(2)?;
+                match words.get(1) {
+                    Some(&"ip") => self.program.i = v,
+                    Some(&"rb") => self.program.rb = v,
+                    _ => {
+                        self.program.set(num(1)?, v);
+                    }
+                }
+                Ok(String::new())
+            }
+            "l" | "list" => Ok(self.listing()),
+            "in" | "input" => {
+                for n in 1..words.len() {
+                    self.program.inputs.push_back(num(n)?);
+                }
+                Ok(String::new())
+            }
+            "out" => Ok(format!("{:?}", self.outputs)),
+            "h" | "help" => Ok(HELP.trim_end().to_string()),
+            w => Err(format!("unknown command `{}`, try `help`", w)),
+        }
+    }
+
+    /// Reads commands from `input` until it runs out or sees `quit`.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> io::Result<()> {
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                write!(out, "> ")?;
+                out.flush()?;
+                continue;
+            }
+            if words[0] == "q" || words[0] == "quit" {
+                break;
+            }
+            match self.command(&words) {
+                Ok(s) if s.is_empty() => {}
+                Ok(s) => writeln!(out, "{}", s)?,
+                Err(e) => writeln!(out, "error: {}", e)?,
+            }
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::QUINE;
+
+    fn quine() -> Debugger {
+        Debugger::new(Program::new(&QUINE))
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut d = quine();
+        d.breakpoints.insert(4);
+
+        assert_eq!(Ok(Stop::Breakpoint(4)), d.cont());
+        assert_eq!(vec![109], d.outputs);
+        assert_eq!(Ok(Stop::Breakpoint(4)), d.cont());
+        assert_eq!(vec![109, 1], d.outputs);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut d = quine();
+        d.watchpoints.insert(100);
+
+        assert_eq!(
+            Ok(Stop::Watchpoint {
+                addr: 100,
+                old: 0,
+                new: 1
+            }),
+            d.cont()
+        );
+        assert_eq!(8, d.program.i);
+    }
+
+    #[test]
+    fn test_needs_input() {
+        let mut d = Debugger::new(Program::new(&[3, 5, 4, 5, 99]));
+
+        assert_eq!(Ok(Stop::NeedsInput), d.cont());
+        d.program.inputs.push_back(7);
+        assert_eq!(Ok(Stop::Halted), d.cont());
+        assert_eq!(vec![7], d.outputs);
+    }
+
+    #[test]
+    fn test_repl() {
+        let mut d = quine();
+        let script = "b 12\nc\nl\nset rb 50\nx 100 2\nfoo\nq\nc\n";
+        let mut out = Vec::new();
+        d.repl(script.as_bytes(), &mut out).unwrap();
+
+        let expected = concat!(
+            "> > breakpoint at 12\n",
+            "=>*    12  jf [101], #0\n",
+            "> ",
+            "        2  out [rb-1]\n",
+            "        4  add [100], #1, [100]\n",
+            "        8  eq [100], #16, [101]\n",
+            "=>*    12  jf [101], #0\n",
+            "       15  hlt\n",
+        );
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(expected), "{}", out);
+        assert!(out.contains("[100] = 1\n[101] = 0\n"));
+        assert!(out.contains("error: unknown command `foo`"));
+        assert_eq!(50, d.program.rb);
+    }
+
+    #[test]
+    fn test_examine() {
+        let mut d = quine();
+        assert_eq!(
+            Ok("[0] = 109\n[1] = 1".to_string()),
+            d.command(&["x", "0", "2"])
+        );
+        let n = d
+            .command(&["x", "0", "1000000000000"])
+            .unwrap()
+            .lines()
+            .count();
+        assert_eq!(MAX_CELLS as usize, n);
+
+        let max = i128::MAX.to_string();
+        assert_eq!(
+            Err("address out of range".to_string()),
+            d.command(&["x", &max, "2"])
+        );
+    }
+
+    #[test]
+    fn test_listing_at_top() {
+        let mut d = quine();
+        let max = i128::MAX.to_string();
+        d.command(&["set", "ip", &max]).unwrap();
+
+        let expected = format!("=> {}  DATA 0", max);
+        assert_eq!(Ok(expected), d.command(&["l"]));
+    }
+}
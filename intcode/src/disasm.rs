@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::instruction::{operand, Instruction, Mode, Op};
+
+const DATA_PER_LINE: usize = 8;
+
+fn fetch(t: &[i128], i: i128) -> i128 {
+    match usize::try_from(i) {
+        Ok(i) => t.get(i).copied().unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+// Compiled programs call a subroutine by storing a constant return address
+// at [rb+0] and then jumping unconditionally; the callee comes back through
+// a computed jump on [rb+0].
+pub(crate) fn return_address(t: &[i128], a: usize, ins: &Instruction) -> Option<usize> {
+    let ret = match (ins.op, ins.params().collect::<Vec<_>>().as_slice()) {
+        (Op::Add, [(Mode::Immediate, x), (Mode::Immediate, 0), (Mode::Relative, 0)])
+        | (Op::Add, [(Mode::Immediate, 0), (Mode::Immediate, x), (Mode::Relative, 0)])
+        | (Op::Mul, [(Mode::Immediate, x), (Mode::Immediate, 1), (Mode::Relative, 0)])
+        | (Op::Mul, [(Mode::Immediate, 1), (Mode::Immediate, x), (Mode::Relative, 0)]) => *x,
+        _ => return None,
+    };
+    let next = a + ins.size();
+    let jump = Instruction::decode(next as i128, |i| fetch(t, i)).ok()?;
+    if jump.target().is_none() || jump.falls_through() {
+        return None;
+    }
+    usize::try_from(ret).ok()
+}
+
+/// Decodes every instruction reachable from address 0 by following
+/// fall-through, jumps with immediate targets and the return addresses of
+/// subroutine calls. Other computed jumps aren't followed, so code only
+/// reached that way shows up as data.
+pub fn reachable(t: &[i128]) -> BTreeMap<usize, Instruction> {
+    let mut code = BTreeMap::new();
+    let mut todo = vec![0];
+
+    while let Some(a) = todo.pop() {
+        if a >= t.len() || code.contains_key(&a) {
+            continue;
+        }
+        let ins = match Instruction::decode(a as i128, |i| fetch(t, i)) {
+            Ok(ins) => ins,
+            Err(_) => continue,
+        };
+        // targets past a usize can't be in `t`
+        if let Some(Ok(target)) = ins.target().map(usize::try_from) {
+            todo.push(target);
+        }
+        if let Some(ret) = return_address(t, a, &ins) {
+            todo.push(ret);
+        }
+        if ins.falls_through() {
+            todo.push(a + ins.size());
+        }
+        code.insert(a, ins);
+    }
+
+    code
+}
+
+fn label(a: i128) -> String {
+    format!("L{}", a)
+}
+
+fn render(ins: &Instruction, labels: &BTreeSet<usize>) -> String {
+    let mut s = ins.op.name().to_string();
+    for (n, (m, x)) in ins.params().enumerate() {
+        s += if n == 0 { " " } else { ", " };
+        let is_label = usize::try_from(x).is_ok_and(|x| labels.contains(&x));
+        if Some(x) == ins.target() && m == Mode::Immediate && is_label {
+            s += &format!("#{}", label(x));
+        } else {
+            s += &operand(m, x);
+        }
+    }
+    s
+}
+
+/// Renders `t` as one instruction per line, prefixed with its address. Jump
+/// targets get an `L<addr>:` label and anything not reachable from address 0
+/// is dumped as `DATA`.
+pub fn disassemble(t: &[i128]) -> String {
+    let code = reachable(t);
+    let labels: BTreeSet<usize> = code
+        .values()
+        .filter_map(|ins| usize::try_from(ins.target()?).ok())
+        .filter(|x| *x < t.len())
+        .collect();
+
+    let mut out = String::new();
+    let mut a = 0;
+    while a < t.len() {
+        if labels.contains(&a) {
+            writeln!(out, "{}:", label(a as i128)).unwrap();
+        }
+        if let Some(ins) = code.get(&a) {
+            writeln!(out, "{:>6}  {}", a, render(ins, &labels)).unwrap();
+            a += ins.size();
+            continue;
+        }
+
+        let start = a;
+        let mut data = Vec::new();
+        while a < t.len()
+            && !code.contains_key(&a)
+            && data.len() < DATA_PER_LINE
+            && (a == start || !labels.contains(&a))
+        {
+            data.push(t[a].to_string());
+            a += 1;
+        }
+        writeln!(out, "{:>6}  DATA {}", start, data.join(", ")).unwrap();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::QUINE;
+
+    #[test]
+    fn test_disassemble() {
+        let expected = concat!(
+            "L0:\n",
+            "     0  arb #1\n",
+            "     2  out [rb-1]\n",
+            "     4  add [100], #1, [100]\n",
+            "     8  eq [100], #16, [101]\n",
+            "    12  jf [101], #L0\n",
+            "    15  hlt\n",
+        );
+
+        assert_eq!(expected, disassemble(&QUINE));
+    }
+
+    #[test]
+    fn test_return_address() {
+        // call L10, which returns to the hlt at 9
+        let t = vec![109, 20, 21101, 9, 0, 0, 1105, 1, 10, 99, 2105, 1, 0];
+        let code = reachable(&t);
+
+        assert!(code.contains_key(&9));
+        assert!(code.contains_key(&10));
+    }
+
+    #[test]
+    fn test_unreachable_data() {
+        let t = vec![1105, 1, 7, 1, 2, 3, 4, 4, 3, 99];
+        let expected = concat!(
+            "     0  jt #1, #L7\n",
+            "     3  DATA 1, 2, 3, 4\n",
+            "L7:\n",
+            "     7  out [3]\n",
+            "     9  hlt\n",
+        );
+
+        assert_eq!(expected, disassemble(&t));
+    }
+
+    #[test]
+    fn test_far_target() {
+        // 2^64 isn't address 0, so it gets no label and 0 isn't followed
+        // as the jump
+        let t = vec![1105, 1, 1 << 64, 99];
+        let expected = concat!(
+            "     0  jt #1, #18446744073709551616\n",
+            "     3  DATA 99\n",
+        );
+
+        assert_eq!(expected, disassemble(&t));
+        assert_eq!(vec![0], reachable(&t).into_keys().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::VmState;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    UnknownOpcode {
+        ip: i128,
+        value: i128,
+    },
+    BadMode {
+        ip: i128,
+        mode: i128,
+    },
+    NegativeAddress {
+        ip: i128,
+        addr: i128,
+    },
+    InputExhausted {
+        ip: i128,
+    },
+    InstructionLimitExceeded {
+        limit: usize,
+        state: Box<VmState>,
+    },
+    CellLimitExceeded {
+        limit: usize,
+        state: Box<VmState>,
+    },
+    AddressLimitExceeded {
+        limit: i128,
+        addr: i128,
+        state: Box<VmState>,
+    },
+    OutputLimitExceeded {
+        limit: usize,
+        state: Box<VmState>,
+    },
+    DeadlineExceeded {
+        state: Box<VmState>,
+    },
+    Overflow {
+        ip: i128,
+    },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { ip, value } => {
+                write!(f, "unknown opcode {} at {}", value, ip)
+            }
+            IntcodeError::BadMode { ip, mode } => {
+                write!(f, "bad parameter mode {} at {}", mode, ip)
+            }
+            IntcodeError::NegativeAddress { ip, addr } => {
+                write!(f, "negative address {} at {}", addr, ip)
+            }
+            IntcodeError::InputExhausted { ip } => write!(f, "input exhausted at {}", ip),
+            IntcodeError::InstructionLimitExceeded { limit, state } => {
+                write!(f, "instruction limit {} exceeded at {}", limit, state)
+            }
+            IntcodeError::CellLimitExceeded { limit, state } => {
+                write!(f, "cell limit {} exceeded at {}", limit, state)
+            }
+            IntcodeError::AddressLimitExceeded { limit, addr, state } => {
+                write!(f, "address {} past the limit {} at {}", addr, limit, state)
+            }
+            IntcodeError::OutputLimitExceeded { limit, state } => {
+                write!(f, "output limit {} exceeded at {}", limit, state)
+            }
+            IntcodeError::DeadlineExceeded { state } => write!(f, "deadline passed at {}", state),
+            IntcodeError::Overflow { ip } => write!(f, "arithmetic overflow at {}", ip),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,21 @@
+// Puzzle programs the tests around the crate share.
+
+// day9's quine, which outputs a copy of itself
+pub(crate) const QUINE: [i128; 16] = [
+    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+];
+
+// day5's larger example: outputs 999, 1000 or 1001 for an input below, at
+// or above 8
+pub(crate) const DAY5: [i128; 47] = [
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+    1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105,
+    1, 46, 98, 99,
+];
+
+// day7's feedback loop example, which settles on 139629729 for the phases
+// 9, 8, 7, 6, 5
+pub(crate) const FEEDBACK: [i128; 29] = [
+    3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28, 1005,
+    28, 6, 99, 0, 0, 5,
+];
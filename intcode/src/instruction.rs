@@ -0,0 +1,256 @@
+use std::fmt;
+
+use crate::IntcodeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jt,
+    Jf,
+    Lt,
+    Eq,
+    Arb,
+    Hlt,
+}
+
+const OPS: [Op; 10] = [
+    Op::Add,
+    Op::Mul,
+    Op::In,
+    Op::Out,
+    Op::Jt,
+    Op::Jf,
+    Op::Lt,
+    Op::Eq,
+    Op::Arb,
+    Op::Hlt,
+];
+
+impl Op {
+    pub fn from_code(c: i128) -> Option<Op> {
+        OPS.iter().copied().find(|op| op.code() == c)
+    }
+
+    pub fn from_name(s: &str) -> Option<Op> {
+        OPS.iter().copied().find(|op| op.name() == s)
+    }
+
+    pub fn code(self) -> i128 {
+        match self {
+            Op::Add => 1,
+            Op::Mul => 2,
+            Op::In => 3,
+            Op::Out => 4,
+            Op::Jt => 5,
+            Op::Jf => 6,
+            Op::Lt => 7,
+            Op::Eq => 8,
+            Op::Arb => 9,
+            Op::Hlt => 99,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Mul => "mul",
+            Op::In => "in",
+            Op::Out => "out",
+            Op::Jt => "jt",
+            Op::Jf => "jf",
+            Op::Lt => "lt",
+            Op::Eq => "eq",
+            Op::Arb => "arb",
+            Op::Hlt => "hlt",
+        }
+    }
+
+    pub fn params(self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => 3,
+            Op::Jt | Op::Jf => 2,
+            Op::In | Op::Out | Op::Arb => 1,
+            Op::Hlt => 0,
+        }
+    }
+
+    /// The parameter the instruction writes to, if any. It's always the last.
+    pub fn dest(self) -> Option<usize> {
+        match self {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => Some(2),
+            Op::In => Some(0),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    pub fn from_code(m: i128) -> Option<Mode> {
+        match m {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i128 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+/// One decoded instruction. Parameters past `op.params()` are unused and
+/// left as position-mode zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub op: Op,
+    pub modes: [Mode; 3],
+    pub operands: [i128; 3],
+}
+
+impl Instruction {
+    /// Decodes the instruction at `ip`, reading memory through `get`. Only the
+    /// modes of parameters the opcode actually uses are checked, the same as
+    /// the interpreter.
+    pub fn decode<F: Fn(i128) -> i128>(ip: i128, get: F) -> Result<Instruction, IntcodeError> {
+        let t = get(ip);
+        let op = match Op::from_code(t % 100) {
+            Some(op) => op,
+            None => return Err(IntcodeError::UnknownOpcode { ip, value: t }),
+        };
+
+        let mut modes = [Mode::Position; 3];
+        let mut operands = [0; 3];
+        let mut div = 100;
+        for n in 0..op.params() {
+            let m = (t / div) % 10;
+            modes[n] = match Mode::from_code(m) {
+                Some(Mode::Immediate) if op.dest() == Some(n) => None,
+                mode => mode,
+            }
+            .ok_or(IntcodeError::BadMode { ip, mode: m })?;
+            let a = ip
+                .checked_add(1 + n as i128)
+                .ok_or(IntcodeError::Overflow { ip })?;
+            operands[n] = get(a);
+            div *= 10;
+        }
+
+        Ok(Instruction {
+            op,
+            modes,
+            operands,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        1 + self.op.params()
+    }
+
+    pub fn encode(&self) -> Vec<i128> {
+        let mut t = self.op.code();
+        let mut mul = 100;
+        for m in &self.modes[..self.op.params()] {
+            t += m.code() * mul;
+            mul *= 10;
+        }
+        let mut v = vec![t];
+        v.extend_from_slice(&self.operands[..self.op.params()]);
+        v
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = (Mode, i128)> + '_ {
+        self.modes
+            .iter()
+            .copied()
+            .zip(self.operands.iter().copied())
+            .take(self.op.params())
+    }
+
+    /// Where a jump goes when its target is known without running the
+    /// program.
+    pub fn target(&self) -> Option<i128> {
+        match self.op {
+            Op::Jt | Op::Jf if self.modes[1] == Mode::Immediate => Some(self.operands[1]),
+            _ => None,
+        }
+    }
+
+    /// Whether execution can continue at the next instruction.
+    pub fn falls_through(&self) -> bool {
+        match (self.op, self.modes[0]) {
+            (Op::Hlt, _) => false,
+            (Op::Jt, Mode::Immediate) => self.operands[0] == 0,
+            (Op::Jf, Mode::Immediate) => self.operands[0] != 0,
+            _ => true,
+        }
+    }
+}
+
+pub fn operand(m: Mode, x: i128) -> String {
+    match m {
+        Mode::Position => format!("[{}]", x),
+        Mode::Immediate => format!("#{}", x),
+        Mode::Relative if x < 0 => format!("[rb-{}]", x.unsigned_abs()),
+        Mode::Relative => format!("[rb+{}]", x),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.op.name())?;
+        for (n, (m, x)) in self.params().enumerate() {
+            let sep = if n == 0 { " " } else { ", " };
+            write!(f, "{}{}", sep, operand(m, x))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(t: &[i128]) -> Result<Instruction, IntcodeError> {
+        Instruction::decode(0, |i| t.get(i as usize).copied().unwrap_or(0))
+    }
+
+    #[test]
+    fn test_decode() {
+        let ins = decode(&[21201, -3, 7, 4]).unwrap();
+
+        assert_eq!(Op::Add, ins.op);
+        assert_eq!([Mode::Relative, Mode::Immediate, Mode::Relative], ins.modes);
+        assert_eq!("add [rb-3], #7, [rb+4]", ins.to_string());
+        assert_eq!(vec![21201, -3, 7, 4], ins.encode());
+
+        let ins = decode(&[204, i128::MIN]).unwrap();
+        assert_eq!(format!("out [rb-{}]", 1u128 << 127), ins.to_string());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            Err(IntcodeError::UnknownOpcode { ip: 0, value: 42 }),
+            decode(&[42])
+        );
+        assert_eq!(
+            Err(IntcodeError::BadMode { ip: 0, mode: 1 }),
+            decode(&[103, 0])
+        );
+    }
+}
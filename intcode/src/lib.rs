@@ -0,0 +1,53 @@
+mod ascii;
+mod asm;
+mod cfg;
+mod compile;
+#[cfg(test)]
+mod conformance;
+mod debugger;
+mod disasm;
+mod error;
+#[cfg(test)]
+mod fixtures;
+mod instruction;
+mod limits;
+mod load;
+mod memory;
+mod network;
+mod patch;
+mod pipeline;
+mod profile;
+mod program;
+mod search;
+mod snapshot;
+mod solver;
+mod stream;
+mod symbolic;
+mod threaded;
+mod trace;
+mod varint;
+mod word;
+
+pub use ascii::{Ascii, NotAscii, Text};
+pub use asm::{assemble, AsmError};
+pub use cfg::{Block, Cfg, Edge};
+pub use compile::Compiled;
+pub use debugger::{Debugger, Stop};
+pub use disasm::{disassemble, reachable};
+pub use error::IntcodeError;
+pub use instruction::{Instruction, Mode, Op};
+pub use limits::{Limits, VmState};
+pub use load::{decode, encode, load, parse, LoadError};
+pub use memory::{DenseMemory, HashMemory, Memory, PagedMemory};
+pub use network::{Network, Node, Packet, IDLE};
+pub use patch::{diff, Change, Patch, PatchError, Patches};
+pub use pipeline::{link, Pipeline, PipelineError};
+pub use profile::{Gap, Profile};
+pub use program::{Program, State, StepOutcome};
+pub use search::{argmax_by, find_by, grid, permutations, Run, Search, Seed};
+pub use snapshot::Snapshot;
+pub use solver::{Linear, Problem, Smt, Solver};
+pub use stream::AsyncError;
+pub use symbolic::{Constraint, End, Expr, Goal, Path, Rel, Symbolic, SymbolicError};
+pub use trace::{diverge, Event, Trace};
+pub use word::{convert, Word};
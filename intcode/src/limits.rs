@@ -0,0 +1,45 @@
+use std::fmt;
+use std::time::Instant;
+
+/// Bounds on how far a program may get before it's stopped, for running
+/// programs that might never halt or might eat all the memory. Everything
+/// is unlimited by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Instructions executed.
+    pub instructions: Option<usize>,
+    /// Distinct cells read or written by instructions. Fetching the
+    /// instructions themselves doesn't count.
+    pub cells: Option<usize>,
+    /// The largest address that may be read or written.
+    pub address: Option<i128>,
+    /// Values output.
+    pub outputs: Option<usize>,
+    /// When to give up, by the wall clock. Only checked every
+    /// `DEADLINE_EVERY` instructions, so it can be overshot a little.
+    pub deadline: Option<Instant>,
+}
+
+// How many instructions run between looks at the clock.
+pub(crate) const DEADLINE_EVERY: usize = 1024;
+
+/// Where a program was when a limit stopped it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmState {
+    pub ip: i128,
+    pub rb: i128,
+    pub instructions: usize,
+    /// Only counted while there's a cell limit.
+    pub cells: usize,
+    pub outputs: usize,
+}
+
+impl fmt::Display for VmState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ip {}, rb {} after {} instructions, {} cells and {} outputs",
+            self.ip, self.rb, self.instructions, self.cells, self.outputs
+        )
+    }
+}
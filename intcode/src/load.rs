@@ -0,0 +1,207 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::varint;
+
+const MAGIC: &[u8; 4] = b"ICP1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Io(String),
+    /// Something unreadable `offset` bytes into the file.
+    Syntax {
+        offset: usize,
+        msg: String,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(msg) => write!(f, "{}", msg),
+            LoadError::Syntax { offset, msg } => write!(f, "byte {}: {}", offset, msg),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+fn syntax<T>(offset: usize, msg: String) -> Result<T, LoadError> {
+    Err(LoadError::Syntax { offset, msg })
+}
+
+/// Parses a program written as numbers separated by commas, whitespace or
+/// both, so the puzzle input's trailing newline and programs spread over
+/// several lines both work. A `;` or `#` starts a comment running to the end
+/// of the line. A comma with no number since the last one is an error, but
+/// a trailing comma is fine.
+pub fn parse(s: &str) -> Result<Vec<i128>, LoadError> {
+    let mut t = Vec::new();
+    // whether there's been a number since the last comma
+    let mut field = false;
+    let mut start = None;
+    let mut comment = false;
+    let end = (s.len(), '\n');
+    for (n, c) in s.char_indices().chain(std::iter::once(end)) {
+        if comment {
+            comment = c != '\n';
+            continue;
+        }
+        let sep = c == ',' || c == ';' || c == '#' || c.is_whitespace();
+        if let (Some(a), true) = (start, sep) {
+            match s[a..n].parse() {
+                Ok(x) => t.push(x),
+                Err(_) => return syntax(a, format!("bad number `{}`", &s[a..n])),
+            }
+            start = None;
+            field = true;
+        }
+        match c {
+            ',' if !field => return syntax(n, "empty field".to_string()),
+            ',' => field = false,
+            ';' | '#' => comment = true,
+            _ if sep || start.is_some() => {}
+            _ => start = Some(n),
+        }
+    }
+    Ok(t)
+}
+
+/// The program in the compact binary format: a magic number, the length,
+/// then each value as a zigzag varint.
+pub fn encode(t: &[i128]) -> Vec<u8> {
+    let mut b = MAGIC.to_vec();
+    varint::write(&mut b, t.len() as i128).unwrap();
+    for x in t {
+        varint::write(&mut b, *x).unwrap();
+    }
+    b
+}
+
+/// Reads a program written by `encode`.
+pub fn decode(b: &[u8]) -> Result<Vec<i128>, LoadError> {
+    if !b.starts_with(MAGIC) {
+        return syntax(0, "not a binary Intcode program".to_string());
+    }
+    let mut r = &b[MAGIC.len()..];
+    let next = |r: &mut &[u8]| {
+        let offset = b.len() - r.len();
+        match varint::read(r) {
+            Ok(Some(x)) => Ok(x),
+            Ok(None) => syntax(offset, "unexpected end of file".to_string()),
+            Err(e) => syntax(offset, e.to_string()),
+        }
+    };
+    let len = match usize::try_from(next(&mut r)?) {
+        Ok(len) => len,
+        Err(_) => return syntax(MAGIC.len(), "bad length".to_string()),
+    };
+    let mut t = Vec::new();
+    for _ in 0..len {
+        t.push(next(&mut r)?);
+    }
+    if !r.is_empty() {
+        return syntax(b.len() - r.len(), "trailing bytes".to_string());
+    }
+    Ok(t)
+}
+
+/// Loads a program from a file in either format.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<i128>, LoadError> {
+    let b = fs::read(path).map_err(|e| LoadError::Io(e.to_string()))?;
+    if b.starts_with(MAGIC) {
+        return decode(&b);
+    }
+    match std::str::from_utf8(&b) {
+        Ok(s) => parse(s),
+        Err(e) => syntax(e.valid_up_to(), "not UTF-8 text".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        assert_eq!(Ok(vec![1, 0, 0, 3, 99]), parse("1,0,0,3,99\n"));
+        assert_eq!(Ok(vec![1, -2, 3]), parse(" 1 , -2,\r\n3 "));
+        assert_eq!(Ok(vec![]), parse(""));
+
+        let commented = "
+            ; day5's echo
+            3, 0,   # read
+            4, 0,   # write
+            99
+        ";
+        assert_eq!(Ok(vec![3, 0, 4, 0, 99]), parse(commented));
+        assert_eq!(Ok(vec![3, 0, 4, 0, 99]), parse("3 0\n4 0\n99,"));
+    }
+
+    #[test]
+    fn test_text_errors() {
+        let err = |offset, msg: &str| {
+            Err(LoadError::Syntax {
+                offset,
+                msg: msg.to_string(),
+            })
+        };
+        assert_eq!(err(2, "empty field"), parse("1,,2"));
+        assert_eq!(err(0, "empty field"), parse(",1"));
+        assert_eq!(err(4, "empty field"), parse("1,\n\n,2"));
+        assert_eq!(err(4, "bad number `x2`"), parse("1,  x2,3"));
+        assert_eq!(
+            err(0, "bad number `999999999999999999999999999999999999999999`"),
+            parse("999999999999999999999999999999999999999999")
+        );
+    }
+
+    #[test]
+    fn test_binary() {
+        let t = vec![1, -1, i128::MAX, i128::MIN, 99];
+        let b = encode(&t);
+
+        assert_eq!(Ok(t), decode(&b));
+        assert!(decode(&b[..b.len() - 1]).is_err());
+        assert_eq!(
+            Err(LoadError::Syntax {
+                offset: 0,
+                msg: "not a binary Intcode program".to_string()
+            }),
+            decode(b"1,2,3")
+        );
+
+        let mut b = MAGIC.to_vec();
+        varint::write(&mut b, -1).unwrap();
+        assert_eq!(
+            Err(LoadError::Syntax {
+                offset: 4,
+                msg: "bad length".to_string()
+            }),
+            decode(&b)
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        // named for the process so concurrent runs don't share files
+        let dir = std::env::temp_dir();
+        let name = format!("intcode-load-test-{}", std::process::id());
+        let text = dir.join(format!("{}.txt", name));
+        let bin = dir.join(format!("{}.icp", name));
+        fs::write(&text, "104,7,99\n").unwrap();
+        fs::write(&bin, encode(&[104, 7, 99])).unwrap();
+
+        assert_eq!(Ok(vec![104, 7, 99]), load(&text));
+        assert_eq!(Ok(vec![104, 7, 99]), load(&bin));
+        assert!(matches!(
+            load(dir.join(format!("{}-missing", name))),
+            Err(LoadError::Io(_))
+        ));
+        fs::remove_file(text).unwrap();
+        fs::remove_file(bin).unwrap();
+    }
+}
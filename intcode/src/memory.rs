@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use crate::Word;
+
+const PAGE: usize = 1024;
+const DENSE_LIMIT: i128 = 1 << 20;
+
+/// Backing store for a `Program`'s memory. Unwritten cells read as zero.
+pub trait Memory<W = i128>: Send {
+    fn get(&self, a: i128) -> W;
+    fn set(&mut self, a: i128, v: W);
+    /// The non-zero cells, in any order.
+    fn cells(&self) -> Vec<(i128, W)>;
+    fn clear(&mut self);
+    fn box_clone(&self) -> Box<dyn Memory<W>>;
+}
+
+impl<W> Clone for Box<dyn Memory<W>> {
+    fn clone(&self) -> Box<dyn Memory<W>> {
+        self.box_clone()
+    }
+}
+
+/// The original backend: one hash map entry per cell.
+#[derive(Clone)]
+pub struct HashMemory<W = i128>(HashMap<i128, W>);
+
+impl<W: Word> HashMemory<W> {
+    pub fn new() -> HashMemory<W> {
+        HashMemory(HashMap::new())
+    }
+}
+
+impl<W: Word> Default for HashMemory<W> {
+    fn default() -> HashMemory<W> {
+        HashMemory::new()
+    }
+}
+
+impl<W: Word> Memory<W> for HashMemory<W> {
+    fn get(&self, a: i128) -> W {
+        match self.0.get(&a) {
+            Some(v) => v.clone(),
+            None => W::zero(),
+        }
+    }
+
+    fn set(&mut self, a: i128, v: W) {
+        self.0.insert(a, v);
+    }
+
+    fn cells(&self) -> Vec<(i128, W)> {
+        self.0
+            .iter()
+            .filter(|(_, v)| !v.is_zero())
+            .map(|(a, v)| (*a, v.clone()))
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn box_clone(&self) -> Box<dyn Memory<W>> {
+        Box::new(self.clone())
+    }
+}
+
+/// Fixed-size pages allocated the first time something is written to them,
+/// so a few far-off addresses don't cost a huge allocation.
+#[derive(Clone)]
+pub struct PagedMemory<W = i128> {
+    pages: HashMap<i128, Vec<W>>,
+}
+
+impl<W: Word> PagedMemory<W> {
+    pub fn new() -> PagedMemory<W> {
+        PagedMemory {
+            pages: HashMap::new(),
+        }
+    }
+}
+
+impl<W: Word> Default for PagedMemory<W> {
+    fn default() -> PagedMemory<W> {
+        PagedMemory::new()
+    }
+}
+
+impl<W: Word> Memory<W> for PagedMemory<W> {
+    fn get(&self, a: i128) -> W {
+        let (page, offset) = (a.div_euclid(PAGE as i128), a.rem_euclid(PAGE as i128));
+        match self.pages.get(&page) {
+            Some(p) => p[offset as usize].clone(),
+            None => W::zero(),
+        }
+    }
+
+    fn set(&mut self, a: i128, v: W) {
+        let (page, offset) = (a.div_euclid(PAGE as i128), a.rem_euclid(PAGE as i128));
+        if v.is_zero() && !self.pages.contains_key(&page) {
+            return;
+        }
+        self.pages
+            .entry(page)
+            .or_insert_with(|| vec![W::zero(); PAGE])[offset as usize] = v;
+    }
+
+    fn cells(&self) -> Vec<(i128, W)> {
+        let mut cells = Vec::new();
+        for (page, p) in &self.pages {
+            for (offset, v) in p.iter().enumerate() {
+                if !v.is_zero() {
+                    cells.push((page * PAGE as i128 + offset as i128, v.clone()));
+                }
+            }
+        }
+        cells
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+    }
+
+    fn box_clone(&self) -> Box<dyn Memory<W>> {
+        Box::new(self.clone())
+    }
+}
+
+/// A `Vec` indexed by address that grows as the program writes further
+/// out. Addresses past `DENSE_LIMIT` (or negative ones) go to a paged
+/// fallback instead of growing the vector without bound.
+#[derive(Clone)]
+pub struct DenseMemory<W = i128> {
+    cells: Vec<W>,
+    far: PagedMemory<W>,
+}
+
+impl<W: Word> DenseMemory<W> {
+    pub fn new() -> DenseMemory<W> {
+        DenseMemory {
+            cells: Vec::new(),
+            far: PagedMemory::new(),
+        }
+    }
+}
+
+impl<W: Word> Default for DenseMemory<W> {
+    fn default() -> DenseMemory<W> {
+        DenseMemory::new()
+    }
+}
+
+impl<W: Word> Memory<W> for DenseMemory<W> {
+    fn get(&self, a: i128) -> W {
+        if a >= 0 && a < self.cells.len() as i128 {
+            self.cells[a as usize].clone()
+        } else if (0..DENSE_LIMIT).contains(&a) {
+            W::zero()
+        } else {
+            self.far.get(a)
+        }
+    }
+
+    fn set(&mut self, a: i128, v: W) {
+        if !(0..DENSE_LIMIT).contains(&a) {
+            return self.far.set(a, v);
+        }
+        let a = a as usize;
+        if a >= self.cells.len() {
+            if v.is_zero() {
+                return;
+            }
+            self.cells.resize((a + 1).next_power_of_two(), W::zero());
+        }
+        self.cells[a] = v;
+    }
+
+    fn cells(&self) -> Vec<(i128, W)> {
+        let mut cells: Vec<(i128, W)> = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| !v.is_zero())
+            .map(|(a, v)| (a as i128, v.clone()))
+            .collect();
+        cells.extend(self.far.cells());
+        cells
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+        self.far.clear();
+    }
+
+    fn box_clone(&self) -> Box<dyn Memory<W>> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    fn exercise(mut m: Box<dyn Memory>) {
+        let addrs = [0, 1, 1023, 1024, 5000, DENSE_LIMIT + 7, 1 << 100, -3];
+        for (n, a) in addrs.iter().enumerate() {
+            assert_eq!(0, m.get(*a));
+            m.set(*a, n as i128 + 1);
+        }
+        for (n, a) in addrs.iter().enumerate() {
+            assert_eq!(n as i128 + 1, m.get(*a));
+        }
+        m.set(0, 0);
+        assert_eq!(0, m.get(0));
+
+        let mut cells = m.clone().cells();
+        cells.sort();
+        assert_eq!(addrs.len() - 1, cells.len());
+        assert_eq!((-3, 8), cells[0]);
+
+        m.clear();
+        assert!(m.cells().is_empty());
+    }
+
+    #[test]
+    fn test_hash() {
+        exercise(Box::new(HashMemory::new()));
+    }
+
+    #[test]
+    fn test_paged() {
+        exercise(Box::new(PagedMemory::new()));
+    }
+
+    #[test]
+    fn test_dense() {
+        exercise(Box::new(DenseMemory::new()));
+    }
+
+    #[test]
+    fn test_bigint() {
+        let mut m = DenseMemory::<BigInt>::new();
+        let big = BigInt::from(i128::MAX) * BigInt::from(4);
+        m.set(3, big.clone());
+        m.set(1 << 100, big.clone());
+
+        assert_eq!(big, m.get(3));
+        assert_eq!(BigInt::from(0), m.get(4));
+        assert_eq!(2, m.cells().len());
+    }
+}
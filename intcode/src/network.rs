@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+
+use crate::{IntcodeError, Program, State};
+
+/// What a node reads when it asks for input and has nothing queued.
+pub const IDLE: i128 = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub src: i128,
+    pub dest: i128,
+    pub x: i128,
+    pub y: i128,
+}
+
+#[derive(Clone)]
+pub struct Node {
+    pub program: Program,
+    pub queue: VecDeque<Packet>,
+    /// Whether the node read `IDLE` on its last turn.
+    pub idle: bool,
+    pub halted: bool,
+    out: Vec<i128>,
+}
+
+/// N VMs, each booted with its index as its address, that talk by
+/// outputting `dest, x, y` triples.
+///
+/// Nodes take turns in address order. A turn runs a node until it asks for
+/// input, then hands it the next queued packet's `x` and `y`, or `IDLE` if
+/// there isn't one. Packets are delivered as soon as they're sent, so the
+/// run is the same every time.
+#[derive(Clone)]
+pub struct Network {
+    pub nodes: Vec<Node>,
+    /// Packets sent here go to every node but the sender.
+    pub broadcast: Option<i128>,
+    /// Packets sent here are held by the monitor, which resends the latest
+    /// one to address 0 whenever the network goes idle.
+    pub nat: Option<i128>,
+    pub monitor: Option<Packet>,
+    /// Every packet sent, including the monitor's, in order.
+    pub log: Vec<Packet>,
+}
+
+impl Network {
+    pub fn new(t: &[i128], n: usize) -> Network {
+        Network::with_programs((0..n).map(|_| Program::new(t)).collect())
+    }
+
+    pub fn with_programs(programs: Vec<Program>) -> Network {
+        let nodes = programs
+            .into_iter()
+            .enumerate()
+            .map(|(a, mut program)| {
+                program.inputs.push_back(a as i128);
+                Node {
+                    program,
+                    queue: VecDeque::new(),
+                    idle: false,
+                    halted: false,
+                    out: Vec::new(),
+                }
+            })
+            .collect();
+        Network {
+            nodes,
+            broadcast: None,
+            nat: None,
+            monitor: None,
+            log: Vec::new(),
+        }
+    }
+
+    /// Delivers a packet. Packets to an address nobody has are logged and
+    /// dropped.
+    pub fn send(&mut self, p: Packet) {
+        self.log.push(p);
+        if Some(p.dest) == self.nat {
+            self.monitor = Some(p);
+        } else if Some(p.dest) == self.broadcast {
+            for (a, n) in self.nodes.iter_mut().enumerate() {
+                if a as i128 != p.src {
+                    n.queue.push_back(p);
+                }
+            }
+        } else if p.dest >= 0 && (p.dest as usize) < self.nodes.len() {
+            self.nodes[p.dest as usize].queue.push_back(p);
+        }
+    }
+
+    fn turn(&mut self, a: usize) -> Result<(), IntcodeError> {
+        loop {
+            let n = &mut self.nodes[a];
+            match n.program.run()? {
+                State::Halted => {
+                    n.halted = true;
+                    return Ok(());
+                }
+                State::Output(o) => {
+                    n.out.push(o);
+                    if n.out.len() == 3 {
+                        let p = Packet {
+                            src: a as i128,
+                            dest: n.out[0],
+                            x: n.out[1],
+                            y: n.out[2],
+                        };
+                        n.out.clear();
+                        self.send(p);
+                    }
+                }
+                State::NeedsInput => {
+                    match n.queue.pop_front() {
+                        Some(p) => {
+                            n.program.inputs.extend(&[p.x, p.y]);
+                            n.idle = false;
+                        }
+                        None => {
+                            n.program.inputs.push_back(IDLE);
+                            n.idle = true;
+                        }
+                    }
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    pub fn halted(&self) -> bool {
+        self.nodes.iter().all(|n| n.halted)
+    }
+
+    /// Gives every node one turn. If the network was idle for the whole
+    /// round and the monitor is holding a packet, it's sent to address 0 and
+    /// returned.
+    pub fn round(&mut self) -> Result<Option<Packet>, IntcodeError> {
+        let sent = self.log.len();
+        for a in 0..self.nodes.len() {
+            self.turn(a)?;
+        }
+        if self.log.len() != sent || !self.idle() {
+            return Ok(None);
+        }
+
+        match (self.nat, self.monitor) {
+            (Some(nat), Some(p)) => {
+                let p = Packet {
+                    src: nat,
+                    dest: 0,
+                    ..p
+                };
+                self.send(p);
+                Ok(Some(p))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// No node has anything queued and every node that's still running
+    /// read `IDLE` on its last turn.
+    pub fn idle(&self) -> bool {
+        self.nodes
+            .iter()
+            .all(|n| n.queue.is_empty() && (n.idle || n.halted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assemble;
+
+    // Every node but 0 says hello to 0, and anything a node receives is
+    // forwarded to 255.
+    const FORWARD: &str = "
+            in [addr]
+            jf [addr], #loop
+            out #0
+            out [addr]
+            out #1
+    loop:   in [x]
+            eq [x], #-1, [t]
+            jt [t], #loop
+            in [y]
+            out #255
+            out [x]
+            out [y]
+            jt #1, #loop
+    addr:   db 0
+    x:      db 0
+    y:      db 0
+    t:      db 0
+    ";
+
+    #[test]
+    fn test_nat() {
+        let mut net = Network::new(&assemble(FORWARD).unwrap(), 3);
+        net.nat = Some(255);
+
+        let mut nat = Vec::new();
+        for _ in 0..10 {
+            if let Some(p) = net.round().unwrap() {
+                nat.push(p);
+            }
+        }
+
+        let first = net.log.iter().find(|p| p.dest == 255).unwrap();
+        assert_eq!((0, 1, 1), (first.src, first.x, first.y));
+        assert_eq!(
+            Packet {
+                src: 255,
+                dest: 0,
+                x: 2,
+                y: 1
+            },
+            nat[0]
+        );
+        assert!(nat.len() > 1);
+        assert!(nat.iter().all(|p| *p == nat[0]));
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let src = "
+                in [a]
+                jt [a], #listen
+                out #-2
+                out #5
+                out #6
+        listen: in [x]
+                eq [x], #-1, [t]
+                jt [t], #listen
+                in [y]
+                out #255
+                out [a]
+                out [y]
+                hlt
+        a:      db 0
+        x:      db 0
+        y:      db 0
+        t:      db 0
+        ";
+        let mut net = Network::new(&assemble(src).unwrap(), 3);
+        net.broadcast = Some(-2);
+        for _ in 0..5 {
+            net.round().unwrap();
+        }
+
+        let got: Vec<(i128, i128)> = net
+            .log
+            .iter()
+            .filter(|p| p.dest == 255)
+            .map(|p| (p.x, p.y))
+            .collect();
+        assert_eq!(vec![(1, 6), (2, 6)], got);
+        assert!(net.idle());
+        assert!(!net.halted());
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let t = assemble(FORWARD).unwrap();
+        let run = || {
+            let mut net = Network::new(&t, 4);
+            net.nat = Some(255);
+            for _ in 0..6 {
+                net.round().unwrap();
+            }
+            net.log
+        };
+
+        assert_eq!(run(), run());
+    }
+}
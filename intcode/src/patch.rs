@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::Program;
+
+// The most cells one range may fill.
+const MAX_RANGE: i128 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    Io(String),
+    /// Something unreadable on a line, counting from 1.
+    Syntax {
+        line: usize,
+        msg: String,
+    },
+    UnknownPatch(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Io(msg) => write!(f, "{}", msg),
+            PatchError::Syntax { line, msg } => write!(f, "line {}: {}", line, msg),
+            PatchError::UnknownPatch(name) => write!(f, "no patch named `{}`", name),
+        }
+    }
+}
+
+impl Error for PatchError {}
+
+/// Memory writes made to a program before it runs, like day2's noun and
+/// verb.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub name: String,
+    pub writes: Vec<(i128, i128)>,
+}
+
+/// The patches in a patch file, in the order they were written.
+///
+/// Each patch starts with its name in brackets and is followed by lines
+/// of writes: `a = x` sets one cell, `a = x, y, z` sets the cells from `a`
+/// on, and `a..b = x` fills every cell from `a` up to but not including
+/// `b`. A `;` or `#` starts a comment running to the end of the line.
+///
+/// ```text
+/// [alarm]     ; day2's 1202 program alarm
+/// 1 = 12, 2
+///
+/// [free play]
+/// 0 = 2
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Patches(pub Vec<Patch>);
+
+fn syntax<T>(line: usize, msg: &str) -> Result<T, PatchError> {
+    Err(PatchError::Syntax {
+        line,
+        msg: msg.to_string(),
+    })
+}
+
+fn number(line: usize, s: &str) -> Result<i128, PatchError> {
+    match s.trim() {
+        "" => syntax(line, "missing number"),
+        s => s
+            .parse()
+            .or_else(|_| syntax(line, &format!("bad number `{}`", s))),
+    }
+}
+
+fn address(line: usize, s: &str) -> Result<i128, PatchError> {
+    match number(line, s)? {
+        a if a < 0 => syntax(line, &format!("negative address {}", a)),
+        a => Ok(a),
+    }
+}
+
+impl Patches {
+    pub fn parse(s: &str) -> Result<Patches, PatchError> {
+        let mut patches: Vec<Patch> = Vec::new();
+        for (n, l) in s.lines().enumerate() {
+            let line = n + 1;
+            let l = l.split([';', '#']).next().unwrap().trim();
+            if l.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = l.strip_prefix('[') {
+                let name = match name.strip_suffix(']') {
+                    Some(name) => name.trim(),
+                    None => return syntax(line, "unclosed `[`"),
+                };
+                if patches.iter().any(|p| p.name == name) {
+                    return syntax(line, &format!("patch `{}` defined twice", name));
+                }
+                patches.push(Patch {
+                    name: name.to_string(),
+                    writes: Vec::new(),
+                });
+                continue;
+            }
+
+            let patch = match patches.last_mut() {
+                Some(p) => p,
+                None => return syntax(line, "write before the first patch name"),
+            };
+            let (lhs, rhs) = match l.split_once('=') {
+                Some(parts) => parts,
+                None => return syntax(line, "expected `address = value`"),
+            };
+            let values = rhs
+                .split(',')
+                .map(|x| number(line, x))
+                .collect::<Result<Vec<i128>, PatchError>>()?;
+
+            if let Some((a, b)) = lhs.split_once("..") {
+                let (a, b) = (address(line, a)?, address(line, b)?);
+                if b <= a {
+                    return syntax(line, "empty range");
+                }
+                if b - a > MAX_RANGE {
+                    return syntax(line, "range too large");
+                }
+                if values.len() != 1 {
+                    return syntax(line, "a range takes one value");
+                }
+                patch.writes.extend((a..b).map(|a| (a, values[0])));
+            } else {
+                let a = address(line, lhs)?;
+                if a.checked_add(values.len() as i128 - 1).is_none() {
+                    return syntax(line, "address out of range");
+                }
+                patch.writes.extend(
+                    values
+                        .into_iter()
+                        .enumerate()
+                        .map(|(n, x)| (a + n as i128, x)),
+                );
+            }
+        }
+        Ok(Patches(patches))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Patches, PatchError> {
+        let s = fs::read_to_string(path).map_err(|e| PatchError::Io(e.to_string()))?;
+        Patches::parse(&s)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Patch, PatchError> {
+        self.0
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| PatchError::UnknownPatch(name.to_string()))
+    }
+}
+
+/// A cell that differs between two programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub addr: i128,
+    pub before: i128,
+    pub after: i128,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.addr, self.before, self.after)
+    }
+}
+
+/// Every cell that differs between `before` and `after`, sorted by
+/// address. Diffing a program against a copy taken before it was patched
+/// or run shows what the patch or the run changed.
+pub fn diff(before: &Program, after: &Program) -> Vec<Change> {
+    let mut addrs: Vec<i128> = before
+        .cells()
+        .into_iter()
+        .chain(after.cells())
+        .map(|(a, _)| a)
+        .collect();
+    addrs.sort_unstable();
+    addrs.dedup();
+    addrs
+        .into_iter()
+        .map(|a| Change {
+            addr: a,
+            before: before.get(a),
+            after: after.get(a),
+        })
+        .filter(|c| c.before != c.after)
+        .collect()
+}
+
+impl Program {
+    /// Makes the patch's writes and adds its name to `patched`.
+    pub fn patch(&mut self, patch: &Patch) {
+        for (a, x) in &patch.writes {
+            self.set(*a, *x);
+        }
+        self.patched.push(patch.name.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StepOutcome;
+
+    const PATCHES: &str = "
+        [alarm]         ; day2's 1202 program alarm
+        1 = 12, 2
+
+        [clear]
+        # a range stops short of its end
+        8..11 = 0
+        0 = 99
+    ";
+
+    #[test]
+    fn test_parse() {
+        let p = Patches::parse(PATCHES).unwrap();
+
+        assert_eq!(
+            Ok(&Patch {
+                name: "alarm".to_string(),
+                writes: vec![(1, 12), (2, 2)]
+            }),
+            p.get("alarm")
+        );
+        assert_eq!(
+            vec![(8, 0), (9, 0), (10, 0), (0, 99)],
+            p.get("clear").unwrap().writes
+        );
+        assert_eq!(
+            Err(PatchError::UnknownPatch("fix".to_string())),
+            p.get("fix")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |line, msg: &str| {
+            Err(PatchError::Syntax {
+                line,
+                msg: msg.to_string(),
+            })
+        };
+        assert_eq!(
+            err(1, "write before the first patch name"),
+            Patches::parse("1 = 2")
+        );
+        assert_eq!(err(1, "unclosed `[`"), Patches::parse("[a"));
+        assert_eq!(
+            err(3, "patch `a` defined twice"),
+            Patches::parse("[a]\n[b]\n[a]")
+        );
+        assert_eq!(
+            err(2, "expected `address = value`"),
+            Patches::parse("[a]\n1 2")
+        );
+        assert_eq!(err(2, "bad number `x`"), Patches::parse("[a]\n1 = x"));
+        assert_eq!(err(2, "missing number"), Patches::parse("[a]\n1 = 2,,3"));
+        assert_eq!(err(2, "negative address -1"), Patches::parse("[a]\n-1 = 2"));
+        assert_eq!(err(2, "empty range"), Patches::parse("[a]\n5..5 = 0"));
+        assert_eq!(
+            err(2, "address out of range"),
+            Patches::parse(&format!("[a]\n{} = 1, 2", i128::MAX))
+        );
+        assert_eq!(
+            err(2, "a range takes one value"),
+            Patches::parse("[a]\n1..3 = 1, 2")
+        );
+    }
+
+    #[test]
+    fn test_patch_and_diff() {
+        let patches = Patches::parse(PATCHES).unwrap();
+        let t = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let mut p = Program::new(&t);
+        let start = p.clone();
+
+        p.patch(patches.get("alarm").unwrap());
+        let change = |addr, before, after| Change {
+            addr,
+            before,
+            after,
+        };
+        assert_eq!(vec![change(1, 9, 12), change(2, 10, 2)], diff(&start, &p));
+        assert_eq!(vec!["alarm".to_string()], p.patched);
+
+        let patched = p.clone();
+        assert_eq!(Ok(StepOutcome::Halted), p.intcode());
+        assert_eq!(vec![change(0, 1, 100), change(3, 3, 2)], diff(&patched, &p));
+        assert_eq!("0: 1 -> 100", diff(&patched, &p)[0].to_string());
+    }
+}
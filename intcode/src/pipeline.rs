@@ -0,0 +1,336 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use crate::{IntcodeError, Limits, Program, State};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineError {
+    UnknownNode(String),
+    /// Every node still running is waiting on an empty channel.
+    Deadlock {
+        starved: Vec<String>,
+    },
+    Intcode {
+        node: String,
+        error: IntcodeError,
+    },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::UnknownNode(n) => write!(f, "no node named `{}`", n),
+            PipelineError::Deadlock { starved } => {
+                write!(f, "deadlock: {} starved for input", starved.join(", "))
+            }
+            PipelineError::Intcode { node, error } => write!(f, "{}: {}", node, error),
+        }
+    }
+}
+
+impl Error for PipelineError {}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Channel {
+    pub(crate) queue: VecDeque<i128>,
+    pub(crate) last: Option<i128>,
+}
+
+#[derive(Clone)]
+enum Wire {
+    Phase(i128),
+    Limits(Limits),
+    Input(String),
+    Output(String),
+}
+
+#[derive(Clone)]
+pub(crate) struct Node {
+    pub(crate) name: String,
+    pub(crate) program: Program,
+    pub(crate) input: Option<String>,
+    pub(crate) outputs: Vec<String>,
+    pub(crate) halted: bool,
+}
+
+/// A graph of `Program`s joined by named channels.
+///
+/// Each node reads from at most one channel and copies every output to all
+/// of the channels it writes, so several nodes writing one channel fans in
+/// and one node writing several fans out.
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    pub(crate) nodes: Vec<Node>,
+    pub(crate) channels: BTreeMap<String, Channel>,
+    wiring: Vec<(String, Wire)>,
+}
+
+/// The name `chain` and `ring` give the channel from `a` to `b`.
+pub fn link(a: &str, b: &str) -> String {
+    format!("{}->{}", a, b)
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    pub fn node(&mut self, name: &str, program: Program) -> &mut Pipeline {
+        self.nodes.push(Node {
+            name: name.to_string(),
+            program,
+            input: None,
+            outputs: Vec::new(),
+            halted: false,
+        });
+        self
+    }
+
+    /// Queues a value the node reads before anything from its channel, like
+    /// day7's phase settings.
+    pub fn phase(&mut self, name: &str, x: i128) -> &mut Pipeline {
+        self.wiring.push((name.to_string(), Wire::Phase(x)));
+        self
+    }
+
+    /// Stops the whole pipeline with the node's error if it runs past any
+    /// of `limits`.
+    pub fn limits(&mut self, name: &str, limits: Limits) -> &mut Pipeline {
+        self.wiring.push((name.to_string(), Wire::Limits(limits)));
+        self
+    }
+
+    pub fn input(&mut self, name: &str, channel: &str) -> &mut Pipeline {
+        self.channels.entry(channel.to_string()).or_default();
+        self.wiring
+            .push((name.to_string(), Wire::Input(channel.to_string())));
+        self
+    }
+
+    pub fn output(&mut self, name: &str, channel: &str) -> &mut Pipeline {
+        self.channels.entry(channel.to_string()).or_default();
+        self.wiring
+            .push((name.to_string(), Wire::Output(channel.to_string())));
+        self
+    }
+
+    /// Connects each node to the next.
+    pub fn chain(&mut self, names: &[&str]) -> &mut Pipeline {
+        for w in names.windows(2) {
+            let c = link(w[0], w[1]);
+            self.output(w[0], &c).input(w[1], &c);
+        }
+        self
+    }
+
+    /// Connects each node to the next and the last back to the first.
+    pub fn ring(&mut self, names: &[&str]) -> &mut Pipeline {
+        self.chain(names);
+        if let (Some(first), Some(last)) = (names.first(), names.last()) {
+            let c = link(last, first);
+            self.output(last, &c).input(first, &c);
+        }
+        self
+    }
+
+    pub fn send(&mut self, channel: &str, x: i128) -> &mut Pipeline {
+        let c = self.channels.entry(channel.to_string()).or_default();
+        c.queue.push_back(x);
+        self
+    }
+
+    /// The most recent value written to a channel, whether or not anything
+    /// has read it.
+    pub fn last(&self, channel: &str) -> Option<i128> {
+        self.channels.get(channel).and_then(|c| c.last)
+    }
+
+    /// Values written to a channel that nothing has read yet.
+    pub fn pending(&self, channel: &str) -> Vec<i128> {
+        match self.channels.get(channel) {
+            Some(c) => c.queue.iter().copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Applies phase settings and connections in the order they were given,
+    // so a node can be wired up before it's added.
+    pub(crate) fn wire(&mut self) -> Result<(), PipelineError> {
+        for (name, w) in self.wiring.drain(..) {
+            let node = match self.nodes.iter_mut().find(|n| n.name == name) {
+                Some(n) => n,
+                None => return Err(PipelineError::UnknownNode(name)),
+            };
+            match w {
+                Wire::Phase(x) => node.program.inputs.push_back(x),
+                Wire::Limits(l) => node.program.limits = l,
+                Wire::Input(c) => node.input = Some(c),
+                Wire::Output(c) => node.outputs.push(c),
+            }
+        }
+        Ok(())
+    }
+
+    // Runs one node until it halts or waits on an empty channel, returning
+    // whether it executed anything.
+    fn turn(&mut self, n: usize) -> Result<bool, PipelineError> {
+        let Pipeline {
+            nodes, channels, ..
+        } = self;
+        let node = &mut nodes[n];
+        let count = node.program.count;
+        loop {
+            let s = node.program.run().map_err(|error| PipelineError::Intcode {
+                node: node.name.clone(),
+                error,
+            })?;
+            match s {
+                State::Halted => {
+                    node.halted = true;
+                    break;
+                }
+                State::Output(o) => {
+                    for c in &node.outputs {
+                        let c = channels.get_mut(c).unwrap();
+                        c.queue.push_back(o);
+                        c.last = Some(o);
+                    }
+                }
+                State::NeedsInput => {
+                    let x = node
+                        .input
+                        .as_ref()
+                        .and_then(|c| channels.get_mut(c).unwrap().queue.pop_front());
+                    match x {
+                        Some(x) => node.program.inputs.push_back(x),
+                        None => break,
+                    }
+                }
+            }
+        }
+        Ok(node.program.count != count)
+    }
+
+    /// Runs nodes round-robin in the order they were added until every one
+    /// has halted.
+    pub fn run(&mut self) -> Result<(), PipelineError> {
+        self.wire()?;
+        loop {
+            let mut progress = false;
+            for n in 0..self.nodes.len() {
+                if !self.nodes[n].halted {
+                    progress |= self.turn(n)?;
+                }
+            }
+            if self.nodes.iter().all(|n| n.halted) {
+                return Ok(());
+            }
+            if !progress {
+                let starved = self
+                    .nodes
+                    .iter()
+                    .filter(|n| !n.halted)
+                    .map(|n| n.name.clone())
+                    .collect();
+                return Err(PipelineError::Deadlock { starved });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::FEEDBACK;
+
+    const AMPS: [&str; 5] = ["A", "B", "C", "D", "E"];
+
+    #[test]
+    fn test_ring() {
+        let mut p = Pipeline::new();
+        for (name, phase) in AMPS.iter().zip(&[9, 8, 7, 6, 5]) {
+            p.node(name, Program::new(&FEEDBACK)).phase(name, *phase);
+        }
+        p.ring(&AMPS).send(&link("E", "A"), 0);
+
+        assert_eq!(Ok(()), p.run());
+        assert_eq!(Some(139629729), p.last(&link("E", "A")));
+    }
+
+    #[test]
+    fn test_fan_out_and_in() {
+        // echoes its input doubled
+        let double = [3, 9, 102, 2, 9, 9, 4, 9, 99, 0];
+        let mut p = Pipeline::new();
+        p.node("src", Program::new(&double))
+            .node("a", Program::new(&double))
+            .node("b", Program::new(&double))
+            .input("src", "in")
+            .output("src", "x")
+            .output("src", "y")
+            .input("a", "x")
+            .input("b", "y")
+            .output("a", "out")
+            .output("b", "out")
+            .send("in", 1);
+
+        assert_eq!(Ok(()), p.run());
+        assert_eq!(vec![4, 4], p.pending("out"));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let mut p = Pipeline::new();
+        p.node("src", Program::new(&[3, 9, 4, 9, 3, 9, 4, 9, 99, 0]))
+            .node("sink", Program::new(&[3, 5, 3, 5, 99, 0]))
+            .chain(&["src", "sink"])
+            .send(&link("src", "sink"), 7);
+
+        // `src` echoes nothing without input, so `sink` only ever gets the 7
+        assert_eq!(
+            Err(PipelineError::Deadlock {
+                starved: vec!["src".to_string(), "sink".to_string()]
+            }),
+            p.run()
+        );
+
+        p.input("src", "more").send("more", 1).send("more", 2);
+        assert_eq!(Ok(()), p.run());
+        assert_eq!(vec![2], p.pending(&link("src", "sink")));
+    }
+
+    #[test]
+    fn test_limits() {
+        // B never halts, so A waits on it forever
+        let mut p = Pipeline::new();
+        p.node("A", Program::new(&FEEDBACK))
+            .node("B", Program::new(&[1105, 1, 0]))
+            .ring(&["A", "B"])
+            .phase("A", 5)
+            .send(&link("B", "A"), 0)
+            .limits(
+                "B",
+                Limits {
+                    instructions: Some(1000),
+                    ..Limits::default()
+                },
+            );
+
+        match p.run() {
+            Err(PipelineError::Intcode {
+                node,
+                error: IntcodeError::InstructionLimitExceeded { limit: 1000, .. },
+            }) => assert_eq!("B", node),
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_unknown_node() {
+        let mut p = Pipeline::new();
+        p.node("A", Program::new(&[99])).chain(&["A", "B"]);
+
+        assert_eq!(Err(PipelineError::UnknownNode("B".to_string())), p.run());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::instruction::Op;
+
+// How many of each kind of hot spot the report lists.
+const TOP: usize = 10;
+
+/// The stretch of execution leading up to one input or output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub ip: i128,
+    pub op: Op,
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+/// Execution counts gathered while a `Program` runs with profiling on.
+///
+/// Blocks are the runs of instructions between one jump or branch and the
+/// next, keyed by where they were entered. Call frames follow the relative
+/// base: an `arb` that raises it opens a frame named after the block it's
+/// in, which is closed once the base drops back below it. That's how
+/// compiled Intcode allocates stack frames, so frames line up with
+/// subroutine calls.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub addresses: HashMap<i128, u64>,
+    pub opcodes: HashMap<Op, u64>,
+    /// Entries and instructions executed for each block.
+    pub blocks: HashMap<i128, (u64, u64)>,
+    pub gaps: Vec<Gap>,
+    /// Instructions executed under each stack of call frames, outermost
+    /// first.
+    pub stacks: HashMap<Vec<i128>, u64>,
+    block: Option<i128>,
+    // (entry, relative base while inside) for each open frame
+    frames: Vec<(i128, i128)>,
+    since: (Instant, u64),
+    total: u64,
+}
+
+impl Profile {
+    pub fn new(ip: i128, rb: i128) -> Profile {
+        Profile {
+            addresses: HashMap::new(),
+            opcodes: HashMap::new(),
+            blocks: HashMap::new(),
+            gaps: Vec::new(),
+            stacks: HashMap::new(),
+            block: None,
+            frames: vec![(ip, rb)],
+            since: (Instant::now(), 0),
+            total: 0,
+        }
+    }
+
+    /// Instructions counted so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    // Counts one executed instruction at `ip` that left the relative base at
+    // `rb` and continued at `next`.
+    pub(crate) fn record(&mut self, ip: i128, op: Op, rb: i128, next: i128) {
+        self.total += 1;
+        *self.addresses.entry(ip).or_insert(0) += 1;
+        *self.opcodes.entry(op).or_insert(0) += 1;
+
+        let block = *self.block.get_or_insert(ip);
+        let b = self.blocks.entry(block).or_insert((0, 0));
+        if block == ip {
+            b.0 += 1;
+        }
+        b.1 += 1;
+        let fell = ip.checked_add(1 + op.params() as i128) == Some(next);
+        let jumped = !fell || matches!(op, Op::Jt | Op::Jf);
+        self.block = if jumped { None } else { Some(block) };
+
+        let stack: Vec<i128> = self.frames.iter().map(|f| f.0).collect();
+        *self.stacks.entry(stack).or_insert(0) += 1;
+        let top = self.frames.last().unwrap().1;
+        if rb > top {
+            self.frames.push((block, rb));
+        } else {
+            while self.frames.len() > 1 && rb < self.frames.last().unwrap().1 {
+                self.frames.pop();
+            }
+        }
+
+        if matches!(op, Op::In | Op::Out) {
+            let (at, count) = self.since;
+            self.gaps.push(Gap {
+                ip,
+                op,
+                instructions: self.total - count,
+                elapsed: at.elapsed(),
+            });
+            self.since = (Instant::now(), self.total);
+        }
+    }
+
+    /// The stacks in the folded format flamegraph tools read, one
+    /// `outer;inner count` line each, with frames named by address.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(s, n)| {
+                let s: Vec<String> = s.iter().map(|a| a.to_string()).collect();
+                format!("{} {}", s.join(";"), n)
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+}
+
+// The entries with the largest counts, biggest first.
+fn top<K: Copy + Ord, V: Copy, F: Fn(&V) -> u64>(m: &HashMap<K, V>, n: usize, f: F) -> Vec<(K, V)> {
+    let mut v: Vec<(K, V)> = m.iter().map(|(k, v)| (*k, *v)).collect();
+    v.sort_by_key(|(k, v)| (std::cmp::Reverse(f(v)), *k));
+    v.truncate(n);
+    v
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let share = |n: u64| 100.0 * n as f64 / self.total.max(1) as f64;
+        writeln!(f, "{} instructions", self.total)?;
+
+        writeln!(f, "\nopcodes:")?;
+        let mut ops: Vec<(Op, u64)> = self.opcodes.iter().map(|(k, v)| (*k, *v)).collect();
+        ops.sort_by_key(|(op, n)| (std::cmp::Reverse(*n), op.code()));
+        for (op, n) in ops {
+            writeln!(f, "  {:<4} {:>12} {:>6.2}%", op.name(), n, share(n))?;
+        }
+
+        writeln!(f, "\nhottest addresses:")?;
+        for (a, n) in top(&self.addresses, TOP, |n| *n) {
+            writeln!(f, "  {:>6} {:>12} {:>6.2}%", a, n, share(n))?;
+        }
+
+        writeln!(f, "\nhottest blocks:")?;
+        for (a, (entries, n)) in top(&self.blocks, TOP, |b| b.1) {
+            writeln!(
+                f,
+                "  {:>6} {:>12} {:>6.2}%  entered {}",
+                a,
+                n,
+                share(n),
+                entries
+            )?;
+        }
+
+        if !self.gaps.is_empty() {
+            let n = self.gaps.len() as u64;
+            let instructions: u64 = self.gaps.iter().map(|g| g.instructions).sum();
+            let elapsed: Duration = self.gaps.iter().map(|g| g.elapsed).sum();
+            let longest = self.gaps.iter().max_by_key(|g| g.instructions).unwrap();
+            writeln!(f, "\nbetween I/O:")?;
+            writeln!(
+                f,
+                "  {} events, {} instructions and {:?} each on average",
+                n,
+                instructions / n,
+                elapsed / n as u32
+            )?;
+            write!(
+                f,
+                "  longest: {} instructions, {:?}, before {} at {}",
+                longest.instructions,
+                longest.elapsed,
+                longest.op.name(),
+                longest.ip
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Program, State};
+
+    // a subroutine that allocates a frame, called once
+    const SRC: &str = "
+            arb #100
+            add #back, #0, [rb]
+            jt #1, #sub
+    back:   out [x]
+            hlt
+    sub:    arb #2
+            add [x], #1, [x]
+            arb #-2
+            jt #1, [rb]
+    x:      db 41
+    ";
+
+    fn profiled(t: &[i128]) -> (Vec<i128>, Profile) {
+        let mut p = Program::new(t);
+        p.start_profile();
+        let mut out = Vec::new();
+        while let State::Output(o) = p.run().unwrap() {
+            out.push(o);
+        }
+        (out, p.profile.unwrap())
+    }
+
+    #[test]
+    fn test_counts() {
+        let (out, p) = profiled(&assemble(SRC).unwrap());
+
+        assert_eq!(vec![42], out);
+        assert_eq!(8, p.total());
+        assert_eq!(Some(&3), p.opcodes.get(&Op::Arb));
+        assert_eq!(None, p.opcodes.get(&Op::Hlt));
+        assert_eq!(Some(&1), p.addresses.get(&14));
+        assert_eq!(Some(&(1, 3)), p.blocks.get(&0));
+        assert_eq!(Some(&(1, 4)), p.blocks.get(&12));
+        assert_eq!(1, p.gaps.len());
+        assert_eq!(
+            (9, Op::Out, 8),
+            (p.gaps[0].ip, p.gaps[0].op, p.gaps[0].instructions)
+        );
+        assert!(p.to_string().starts_with("8 instructions"));
+    }
+
+    #[test]
+    fn test_folded() {
+        let (_, p) = profiled(&assemble(SRC).unwrap());
+
+        assert_eq!("0 1\n0;0 5\n0;0;12 2", p.folded());
+    }
+
+    #[test]
+    fn test_input_gaps() {
+        // day5's equal-to-8 test, waiting on input partway through
+        let mut p = Program::new(&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        p.start_profile();
+        assert_eq!(Ok(State::NeedsInput), p.run());
+        p.inputs.push_back(8);
+        assert_eq!(Ok(State::Output(1)), p.run());
+
+        let gaps: Vec<(Op, u64)> = p
+            .profile
+            .unwrap()
+            .gaps
+            .iter()
+            .map(|g| (g.op, g.instructions))
+            .collect();
+        assert_eq!(vec![(Op::In, 1), (Op::Out, 2)], gaps);
+    }
+
+    #[test]
+    fn test_top_cell() {
+        // a loop on a jump whose target is the top cell
+        let mut p = Program::new(&[1105, 1, i128::MAX - 2]);
+        for (n, x) in [1105, 1, i128::MAX - 2].iter().enumerate() {
+            p.set(i128::MAX - 2 + n as i128, *x);
+        }
+        p.limits.instructions = Some(3);
+        p.start_profile();
+        assert!(p.run().is_err());
+
+        let p = p.profile.unwrap();
+        assert_eq!(Some(&2), p.addresses.get(&(i128::MAX - 2)));
+        assert_eq!(Some(&(2, 2)), p.blocks.get(&(i128::MAX - 2)));
+    }
+}
@@ -0,0 +1,763 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::time::Instant;
+
+use crate::instruction::{Instruction, Mode, Op};
+use crate::limits::DEADLINE_EVERY;
+use crate::trace;
+use crate::{DenseMemory, IntcodeError, Limits, Memory, Profile, Trace, VmState, Word};
+
+const MAX_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome<W = i128> {
+    Output(W),
+    Halted,
+}
+
+/// Why `run` handed control back to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State<W = i128> {
+    Halted,
+    NeedsInput,
+    Output(W),
+}
+
+/// An Intcode VM. `run` drives it as a coroutine: it returns on every
+/// output, when it halts, and when it blocks on a read with nothing queued.
+///
+/// Arithmetic wraps at the width of `W` unless `checked` is set, in which
+/// case it fails with `Overflow`. Runaway programs can be stopped with
+/// `limits`.
+#[derive(Clone)]
+pub struct Program<W: Word = i128> {
+    // Only written through `store`, which drops what was decoded from it.
+    tokens: Box<dyn Memory<W>>,
+    pub inputs: VecDeque<W>,
+    pub i: i128,
+    pub rb: i128,
+    pub checked: bool,
+    pub limits: Limits,
+    pub count: usize,
+    pub outputs: usize,
+    pub trace: Option<Trace>,
+    pub profile: Option<Profile>,
+    /// The names of the patches applied, in order.
+    pub patched: Vec<String>,
+    // Decoded instructions by address, dropped when written to. Only the
+    // cells the program was loaded into get a slot, so a jump far out
+    // can't grow it; instructions anywhere else go in `far_cache`.
+    cache: Vec<Option<Instruction>>,
+    far_cache: HashMap<i128, Instruction>,
+    // Cells read or written so far, kept only while there's a cell limit.
+    pub(crate) touched: HashSet<i128>,
+}
+
+impl Program {
+    pub fn new(t: &[i128]) -> Program {
+        Program::with_memory(t, Box::new(DenseMemory::new()))
+    }
+}
+
+impl<W: Word> Program<W> {
+    /// Like `new`, for a program already converted to a narrower or wider
+    /// word type.
+    pub fn from_words(t: &[W]) -> Program<W> {
+        Program::with_memory(t, Box::new(DenseMemory::new()))
+    }
+
+    pub fn with_memory(t: &[W], mut tokens: Box<dyn Memory<W>>) -> Program<W> {
+        tokens.clear();
+        for (a, b) in t.iter().enumerate() {
+            tokens.set(a as i128, b.clone());
+        }
+        Program {
+            tokens,
+            inputs: VecDeque::new(),
+            i: 0,
+            rb: 0,
+            checked: false,
+            limits: Limits::default(),
+            count: 0,
+            outputs: 0,
+            trace: None,
+            profile: None,
+            patched: Vec::new(),
+            cache: vec![None; t.len()],
+            far_cache: HashMap::new(),
+            touched: HashSet::new(),
+        }
+    }
+
+    /// Records every instruction executed from here on into `trace`.
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace::new(self));
+    }
+
+    /// Counts every instruction executed from here on into `profile`.
+    pub fn start_profile(&mut self) {
+        self.profile = Some(Profile::new(self.i, self.rb));
+    }
+
+    /// The non-zero memory cells, sorted by address.
+    pub fn cells(&self) -> Vec<(i128, W)> {
+        let mut cells = self.tokens.cells();
+        cells.sort_by_key(|c| c.0);
+        cells
+    }
+
+    pub fn get(&self, i: i128) -> W {
+        self.tokens.get(i)
+    }
+
+    pub fn set(&mut self, i: i128, x: W) {
+        self.store(i, x);
+    }
+
+    // Empties memory along with every instruction decoded from it.
+    pub(crate) fn clear(&mut self) {
+        self.tokens.clear();
+        self.cache.fill(None);
+        self.far_cache.clear();
+    }
+
+    /// Where the program is now, as reported when a limit stops it.
+    pub fn vm_state(&self) -> VmState {
+        VmState {
+            ip: self.i,
+            rb: self.rb,
+            instructions: self.count,
+            cells: self.touched.len(),
+            outputs: self.outputs,
+        }
+    }
+
+    fn overflow<T>(&self) -> Result<T, IntcodeError> {
+        Err(IntcodeError::Overflow { ip: self.i })
+    }
+
+    // A word used as an address or jump target.
+    fn address(&self, x: &W) -> Result<i128, IntcodeError> {
+        match x.to_i128() {
+            Some(a) => Ok(a),
+            None => self.overflow(),
+        }
+    }
+
+    fn load(&self, i: i128) -> Result<W, IntcodeError> {
+        if i < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.i,
+                addr: i,
+            });
+        }
+        Ok(self.get(i))
+    }
+
+    // Checks an address an instruction is about to read or write against
+    // the limits.
+    fn touch(&mut self, a: i128) -> Result<(), IntcodeError> {
+        if let Some(limit) = self.limits.address {
+            if a > limit {
+                return Err(IntcodeError::AddressLimitExceeded {
+                    limit,
+                    addr: a,
+                    state: Box::new(self.vm_state()),
+                });
+            }
+        }
+        if let Some(limit) = self.limits.cells {
+            if !self.touched.contains(&a) {
+                if self.touched.len() >= limit {
+                    return Err(IntcodeError::CellLimitExceeded {
+                        limit,
+                        state: Box::new(self.vm_state()),
+                    });
+                }
+                self.touched.insert(a);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, a: i128) -> Result<W, IntcodeError> {
+        let x = self.load(a)?;
+        self.touch(a)?;
+        Ok(x)
+    }
+
+    // Where execution carries on after `ins` when it doesn't jump.
+    fn next(&self, ins: &Instruction) -> Result<i128, IntcodeError> {
+        match self.i.checked_add(ins.size() as i128) {
+            Some(i) => Ok(i),
+            None => self.overflow(),
+        }
+    }
+
+    fn offset(&self, x: i128) -> Result<i128, IntcodeError> {
+        match self.rb.checked_add(x) {
+            Some(a) => Ok(a),
+            None => self.overflow(),
+        }
+    }
+
+    // The value of parameter `n`.
+    fn param(&mut self, ins: &Instruction, n: usize) -> Result<W, IntcodeError> {
+        let x = ins.operands[n];
+        match ins.modes[n] {
+            Mode::Position => self.read(x),
+            Mode::Immediate => match W::from_i128(x) {
+                Some(x) => Ok(x),
+                None => self.overflow(),
+            },
+            Mode::Relative => {
+                let a = self.offset(x)?;
+                self.read(a)
+            }
+        }
+    }
+
+    // The address parameter `n` writes to. Decoding already rejected
+    // immediate-mode writes.
+    fn addr(&mut self, ins: &Instruction, n: usize) -> Result<i128, IntcodeError> {
+        let a = match ins.modes[n] {
+            Mode::Relative => self.offset(ins.operands[n])?,
+            _ => ins.operands[n],
+        };
+        if a < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.i,
+                addr: a,
+            });
+        }
+        self.touch(a)?;
+        Ok(a)
+    }
+
+    fn store(&mut self, i: i128, x: W) {
+        self.invalidate(i);
+        self.tokens.set(i, x);
+    }
+
+    // Drops any cached instruction that covers address `i`.
+    fn invalidate(&mut self, i: i128) {
+        if !self.far_cache.is_empty() {
+            for back in 0..MAX_SIZE {
+                if let Some(a) = i.checked_sub(back as i128) {
+                    if self.far_cache.get(&a).is_some_and(|ins| ins.size() > back) {
+                        self.far_cache.remove(&a);
+                    }
+                }
+            }
+        }
+        if i < 0 || i as usize >= self.cache.len() + MAX_SIZE {
+            return;
+        }
+        let i = i as usize;
+        for back in 0..MAX_SIZE.min(i + 1) {
+            if let Some(Some(ins)) = self.cache.get(i - back) {
+                if ins.size() > back {
+                    self.cache[i - back] = None;
+                }
+            }
+        }
+    }
+
+    // The instruction at `ip`, from the cache if it's been decoded before.
+    // With the `uncached` feature it's decoded afresh every time, which is
+    // only useful for measuring what the cache saves.
+    fn decode(&mut self) -> Result<Instruction, IntcodeError> {
+        let ip = self.i;
+        if cfg!(feature = "uncached") {
+            return self.decode_at(ip);
+        }
+        let cached = match usize::try_from(ip).ok().and_then(|i| self.cache.get(i)) {
+            Some(ins) => *ins,
+            None => self.far_cache.get(&ip).copied(),
+        };
+        if let Some(ins) = cached {
+            return Ok(ins);
+        }
+
+        let ins = self.decode_at(ip)?;
+        match usize::try_from(ip).ok().and_then(|i| self.cache.get_mut(i)) {
+            Some(c) => *c = Some(ins),
+            None => {
+                self.far_cache.insert(ip, ins);
+            }
+        }
+        Ok(ins)
+    }
+
+    fn decode_at(&self, ip: i128) -> Result<Instruction, IntcodeError> {
+        let t = self.load(ip)?;
+        if t.to_i128().is_none() {
+            return Err(IntcodeError::UnknownOpcode {
+                ip,
+                value: t.saturating_i128(),
+            });
+        }
+        let ins = Instruction::decode(ip, |i| self.get(i).saturating_i128())?;
+        for n in 0..ins.op.params() {
+            if self.get(ip + 1 + n as i128).to_i128().is_none() {
+                return self.overflow();
+            }
+        }
+        Ok(ins)
+    }
+
+    fn op(
+        &mut self,
+        ins: &Instruction,
+        checked: fn(a: &W, b: &W) -> Option<W>,
+        wrapping: fn(a: &W, b: &W) -> W,
+    ) -> Result<(), IntcodeError> {
+        let a = self.param(ins, 0)?;
+        let b = self.param(ins, 1)?;
+        let c = self.addr(ins, 2)?;
+        let x = if self.checked {
+            match checked(&a, &b) {
+                Some(x) => x,
+                None => return self.overflow(),
+            }
+        } else {
+            wrapping(&a, &b)
+        };
+        let next = self.next(ins)?;
+        self.store(c, x);
+        self.i = next;
+        Ok(())
+    }
+
+    fn branch(&mut self, ins: &Instruction, o: fn(a: &W) -> bool) -> Result<(), IntcodeError> {
+        let a = self.param(ins, 0)?;
+        let b = self.param(ins, 1)?;
+        if o(&a) {
+            self.i = self.address(&b)?;
+        } else {
+            self.i = self.next(ins)?;
+        }
+        Ok(())
+    }
+
+    fn test(&mut self, ins: &Instruction, o: fn(a: &W, b: &W) -> bool) -> Result<(), IntcodeError> {
+        let a = self.param(ins, 0)?;
+        let b = self.param(ins, 1)?;
+        let c = self.addr(ins, 2)?;
+        let next = self.next(ins)?;
+        if o(&a, &b) {
+            self.store(c, W::one());
+        } else {
+            self.store(c, W::zero());
+        }
+        self.i = next;
+        Ok(())
+    }
+
+    /// Like `run`, but treats a read with nothing queued as an error. Handy
+    /// when every input is supplied up front.
+    pub fn intcode(&mut self) -> Result<StepOutcome<W>, IntcodeError> {
+        match self.run()? {
+            State::Output(o) => Ok(StepOutcome::Output(o)),
+            State::Halted => Ok(StepOutcome::Halted),
+            State::NeedsInput => Err(IntcodeError::InputExhausted { ip: self.i }),
+        }
+    }
+
+    /// A halted program stays on its `99`, so calling `run` again keeps
+    /// returning `Halted`.
+    pub fn run(&mut self) -> Result<State<W>, IntcodeError> {
+        loop {
+            if let Some(s) = self.step()? {
+                return Ok(s);
+            }
+        }
+    }
+
+    /// Executes a single instruction, returning the state if it was one that
+    /// `run` would stop on.
+    pub fn step(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        if self.trace.is_none() && self.profile.is_none() {
+            return self.exec();
+        }
+
+        let (ip, rb, count) = (self.i, self.rb, self.count);
+        let opcode = self.get(ip).saturating_i128();
+        let before = match self.trace {
+            Some(_) => trace::begin(self),
+            None => None,
+        };
+        let s = self.exec()?;
+        if let (Some((ins, e)), None) | (Some((ins, e)), Some(State::Output(_))) = (before, &s) {
+            let e = trace::finish(self, ins, e, rb, &s);
+            self.trace.as_mut().unwrap().events.push(e);
+        }
+        if let (Some(p), Some(op)) = (&mut self.profile, Op::from_code(opcode % 100)) {
+            if self.count > count {
+                p.record(ip, op, self.rb, self.i);
+            }
+        }
+        Ok(s)
+    }
+
+    fn exec(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        if let Some(limit) = self.limits.instructions {
+            if self.count >= limit {
+                return Err(IntcodeError::InstructionLimitExceeded {
+                    limit,
+                    state: Box::new(self.vm_state()),
+                });
+            }
+        }
+        if let Some(deadline) = self.limits.deadline {
+            if self.count.is_multiple_of(DEADLINE_EVERY) && Instant::now() >= deadline {
+                return Err(IntcodeError::DeadlineExceeded {
+                    state: Box::new(self.vm_state()),
+                });
+            }
+        }
+        // only instructions that finish are counted, so a limit's state
+        // shows how many ran before the one that failed
+        let ins = self.decode()?;
+        match ins.op {
+            Op::Add => self.op(&ins, W::checked_add, W::wrapping_add)?,
+            Op::Mul => self.op(&ins, W::checked_mul, W::wrapping_mul)?,
+            Op::In => {
+                let a = self.addr(&ins, 0)?;
+                let next = self.next(&ins)?;
+                let i = match self.inputs.pop_front() {
+                    Some(i) => i,
+                    None => return Ok(Some(State::NeedsInput)),
+                };
+                self.store(a, i);
+                self.i = next;
+            }
+            Op::Out => {
+                let o = self.param(&ins, 0)?;
+                let next = self.next(&ins)?;
+                if let Some(limit) = self.limits.outputs {
+                    if self.outputs >= limit {
+                        return Err(IntcodeError::OutputLimitExceeded {
+                            limit,
+                            state: Box::new(self.vm_state()),
+                        });
+                    }
+                }
+                self.outputs += 1;
+                self.count += 1;
+                self.i = next;
+                return Ok(Some(State::Output(o)));
+            }
+            Op::Jt => self.branch(&ins, |a| !a.is_zero())?,
+            Op::Jf => self.branch(&ins, |a| a.is_zero())?,
+            Op::Lt => self.test(&ins, |a, b| a < b)?,
+            Op::Eq => self.test(&ins, |a, b| a == b)?,
+            Op::Arb => {
+                let x = self.param(&ins, 0)?;
+                let x = self.address(&x)?;
+                let rb = self.offset(x)?;
+                self.i = self.next(&ins)?;
+                self.rb = rb;
+            }
+            Op::Hlt => return Ok(Some(State::Halted)),
+        }
+        self.count += 1;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert;
+    use crate::fixtures::QUINE;
+    use num_bigint::BigInt;
+
+    fn outputs(p: &mut Program) -> Vec<i128> {
+        let mut output = Vec::new();
+        while let StepOutcome::Output(o) = p.intcode().unwrap() {
+            output.push(o);
+        }
+        output
+    }
+
+    #[test]
+    fn test_day2() {
+        let mut p = Program::new(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+
+        assert_eq!(Ok(StepOutcome::Halted), p.intcode());
+        assert_eq!(3500, p.get(0));
+    }
+
+    #[test]
+    fn test_day5_compare() {
+        let input = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        for (i, o) in &[(8, 1), (7, 0)] {
+            let mut p = Program::new(&input);
+            p.inputs.push_back(*i);
+            assert_eq!(vec![*o], outputs(&mut p));
+        }
+    }
+
+    #[test]
+    fn test_day9_quine() {
+        let mut p = Program::new(&QUINE);
+
+        assert_eq!(QUINE.to_vec(), outputs(&mut p));
+    }
+
+    #[test]
+    fn test_relative_write() {
+        // add [rb+0] = 7 + 8, then output it
+        let input = vec![109, 10, 21101, 7, 8, 0, 204, 0, 99];
+        let mut p = Program::new(&input);
+
+        assert_eq!(vec![15], outputs(&mut p));
+    }
+
+    #[test]
+    fn test_run_needs_input() {
+        let mut p = Program::new(&[3, 0, 4, 0, 99]);
+        assert_eq!(Ok(State::NeedsInput), p.run());
+        assert_eq!(0, p.i);
+
+        p.inputs.push_back(42);
+        assert_eq!(Ok(State::Output(42)), p.run());
+        assert_eq!(Ok(State::Halted), p.run());
+        assert_eq!(Ok(State::Halted), p.run());
+    }
+
+    #[test]
+    fn test_input_exhausted() {
+        let mut p = Program::new(&[3, 0, 4, 0, 99]);
+
+        assert_eq!(Err(IntcodeError::InputExhausted { ip: 0 }), p.intcode());
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut p = Program::new(&[1101, 1, 1, 5, 42]);
+
+        assert_eq!(
+            Err(IntcodeError::UnknownOpcode { ip: 4, value: 42 }),
+            p.intcode()
+        );
+    }
+
+    #[test]
+    fn test_bad_mode() {
+        let mut p = Program::new(&[11101, 1, 1, 5, 99]);
+        assert_eq!(Err(IntcodeError::BadMode { ip: 0, mode: 1 }), p.intcode());
+
+        let mut p = Program::new(&[304, 0, 99]);
+        assert_eq!(Err(IntcodeError::BadMode { ip: 0, mode: 3 }), p.intcode());
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let mut p = Program::new(&[4, -1, 99]);
+
+        assert_eq!(
+            Err(IntcodeError::NegativeAddress { ip: 0, addr: -1 }),
+            p.intcode()
+        );
+    }
+
+    #[test]
+    fn test_widths() {
+        let square = [3, 9, 2, 9, 9, 9, 4, 9, 99, 0];
+        let big: i128 = 1 << 40;
+
+        let mut p = Program::<i64>::from_words(&convert(&square).unwrap());
+        p.inputs.push_back(1 << 20);
+        assert_eq!(Ok(StepOutcome::Output(1 << 40)), p.intcode());
+
+        let mut p = Program::<i32>::from_words(&convert(&square).unwrap());
+        p.inputs.push_back(1 << 20);
+        assert_eq!(Ok(StepOutcome::Output(0)), p.intcode());
+
+        let mut p = Program::<BigInt>::from_words(&convert(&square).unwrap());
+        p.inputs.push_back(BigInt::from(big * big));
+        let expected = BigInt::from(big).pow(4);
+        assert_eq!(Ok(StepOutcome::Output(expected)), p.intcode());
+    }
+
+    #[test]
+    fn test_checked_overflow() {
+        // day7's amplifiers multiply by 10 on every pass
+        let mut p = Program::<i32>::from_words(&[1002, 7, 10, 7, 1105, 1, 0, 1]);
+        p.checked = true;
+
+        assert_eq!(Err(IntcodeError::Overflow { ip: 0 }), p.intcode());
+        assert_eq!(1_000_000_000, p.get(7));
+
+        let mut p = Program::new(&[1002, 7, 10, 7, 1105, 1, 0, 1]);
+        p.checked = true;
+        p.limits.instructions = Some(200);
+        assert_eq!(Err(IntcodeError::Overflow { ip: 0 }), p.intcode());
+    }
+
+    #[test]
+    fn test_instruction_limit() {
+        let mut p = Program::new(&[1105, 1, 0]);
+        p.limits.instructions = Some(100);
+
+        let state = Box::new(VmState {
+            ip: 0,
+            rb: 0,
+            instructions: 100,
+            cells: 0,
+            outputs: 0,
+        });
+        assert_eq!(
+            Err(IntcodeError::InstructionLimitExceeded { limit: 100, state }),
+            p.intcode()
+        );
+    }
+
+    #[test]
+    fn test_cell_limit() {
+        // counts up in [18], writing each count to the next cell from 100
+        let t = [
+            109, 100, 1001, 18, 1, 18, 21001, 18, 0, 0, 109, 1, 1105, 1, 2, 0, 0, 0, 0,
+        ];
+        let mut p = Program::new(&t);
+        p.limits.cells = Some(5);
+
+        let state = Box::new(VmState {
+            ip: 6,
+            rb: 104,
+            instructions: 18,
+            cells: 5,
+            outputs: 0,
+        });
+        assert_eq!(
+            Err(IntcodeError::CellLimitExceeded { limit: 5, state }),
+            p.intcode()
+        );
+        assert_eq!((4, 0), (p.get(103), p.get(104)));
+    }
+
+    #[test]
+    fn test_address_limit() {
+        // day9 reads and writes well past the end of the program
+        let mut p = Program::new(&[109, 1000, 21101, 7, 8, 5, 204, 5, 99]);
+        p.limits.address = Some(1004);
+
+        let state = Box::new(VmState {
+            ip: 2,
+            rb: 1000,
+            instructions: 1,
+            cells: 0,
+            outputs: 0,
+        });
+        assert_eq!(
+            Err(IntcodeError::AddressLimitExceeded {
+                limit: 1004,
+                addr: 1005,
+                state
+            }),
+            p.intcode()
+        );
+        assert_eq!(0, p.get(1005));
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let mut p = Program::new(&[104, 1, 1105, 1, 0]);
+        p.limits.outputs = Some(3);
+        for _ in 0..3 {
+            assert_eq!(Ok(State::Output(1)), p.run());
+        }
+
+        let state = Box::new(VmState {
+            ip: 0,
+            rb: 0,
+            instructions: 6,
+            cells: 0,
+            outputs: 3,
+        });
+        assert_eq!(
+            Err(IntcodeError::OutputLimitExceeded { limit: 3, state }),
+            p.run()
+        );
+    }
+
+    #[test]
+    fn test_deadline() {
+        let mut p = Program::new(&[1105, 1, 0]);
+        p.limits.deadline = Some(Instant::now());
+
+        match p.intcode() {
+            Err(IntcodeError::DeadlineExceeded { state }) => assert_eq!(0, state.instructions),
+            e => panic!("{:?}", e),
+        }
+
+        p.limits.deadline = Some(Instant::now() + std::time::Duration::from_millis(20));
+        assert!(matches!(
+            p.intcode(),
+            Err(IntcodeError::DeadlineExceeded { .. })
+        ));
+        assert!(p.count > 0);
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // loops back over `out #0` after patching its operand, so the
+        // second run must see the write rather than the cached decode
+        let t = [104, 0, 1001, 1, 7, 1, 1008, 1, 14, 14, 1006, 14, 0, 99, 0];
+        let mut p = Program::new(&t);
+        let mut out = Vec::new();
+        while let StepOutcome::Output(o) = p.intcode().unwrap() {
+            out.push(o);
+        }
+        assert_eq!(vec![0, 7], out);
+
+        // patching the opcode itself through `set`
+        let mut p = Program::new(&[104, 1, 1105, 1, 0]);
+        assert_eq!(Ok(StepOutcome::Output(1)), p.intcode());
+        p.set(0, 99);
+        assert_eq!(Ok(StepOutcome::Halted), p.intcode());
+    }
+
+    #[test]
+    fn test_far_cache() {
+        // a jump far past the end mustn't grow the cache out to it
+        let mut p = Program::new(&[1105, 1, 1_000_000]);
+        p.set(1_000_000, 99);
+        assert_eq!(Ok(StepOutcome::Halted), p.intcode());
+        if !cfg!(feature = "uncached") {
+            assert_eq!((3, 1), (p.cache.len(), p.far_cache.len()));
+        }
+
+        // and a write there still drops what was decoded
+        let mut p = Program::new(&[1105, 1, 1_000_000]);
+        for (n, x) in [104, 5, 1105, 1, 1_000_000].iter().enumerate() {
+            p.set(1_000_000 + n as i128, *x);
+        }
+        assert_eq!(Ok(StepOutcome::Output(5)), p.intcode());
+        p.set(1_000_001, 6);
+        assert_eq!(Ok(StepOutcome::Output(6)), p.intcode());
+    }
+
+    #[test]
+    fn test_top_cell() {
+        // an `out` whose operand is the top cell has nowhere to go next
+        let mut p = Program::new(&[1105, 1, i128::MAX - 1]);
+        p.set(i128::MAX - 1, 104);
+        p.set(i128::MAX, 7);
+        assert_eq!(Err(IntcodeError::Overflow { ip: i128::MAX - 1 }), p.run());
+        assert_eq!(0, p.outputs);
+
+        // but a jump from there that's taken is fine
+        let mut p = Program::new(&[1105, 1, i128::MAX - 2]);
+        for (n, x) in [1105, 1, 0].iter().enumerate() {
+            p.set(i128::MAX - 2 + n as i128, *x);
+        }
+        p.limits.instructions = Some(4);
+        assert!(matches!(
+            p.run(),
+            Err(IntcodeError::InstructionLimitExceeded { .. })
+        ));
+    }
+}
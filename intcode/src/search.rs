@@ -0,0 +1,227 @@
+use std::ops::RangeInclusive;
+
+use rayon::prelude::*;
+
+use crate::{IntcodeError, Program, StepOutcome};
+
+// Candidates are pulled from the space and tried this many at a time, so a
+// lazy space is never collected whole.
+const CHUNK: usize = 1024;
+
+/// How a candidate's parameters get into the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Seed {
+    /// Parameter `n` is written to the `n`th address, like day2's noun and
+    /// verb.
+    Patch(Vec<i128>),
+    /// The parameters are queued as input.
+    Inputs,
+}
+
+/// A candidate's program after it halted, and what it output.
+pub struct Run {
+    pub program: Program,
+    pub outputs: Vec<i128>,
+}
+
+/// Every combination of one value from each range.
+pub fn grid(ranges: &[RangeInclusive<i128>]) -> Vec<Vec<i128>> {
+    let mut out = vec![Vec::new()];
+    for r in ranges {
+        out = out
+            .into_iter()
+            .flat_map(|p| {
+                r.clone().map(move |x| {
+                    let mut p = p.clone();
+                    p.push(x);
+                    p
+                })
+            })
+            .collect();
+    }
+    out
+}
+
+/// Every ordering of `xs`.
+pub fn permutations(xs: &[i128]) -> Vec<Vec<i128>> {
+    if xs.is_empty() {
+        return vec![Vec::new()];
+    }
+    let mut out = Vec::new();
+    for (n, x) in xs.iter().enumerate() {
+        let mut rest = xs.to_vec();
+        rest.remove(n);
+        for mut p in permutations(&rest) {
+            p.insert(0, *x);
+            out.push(p);
+        }
+    }
+    out
+}
+
+/// The first candidate, in the order `space` yields them, that `f` accepts.
+/// Candidates are tried in parallel a chunk at a time, so `space` can be
+/// lazy or even endless as long as something in it matches.
+pub fn find_by<S, F>(space: S, f: F) -> Option<Vec<i128>>
+where
+    S: IntoIterator<Item = Vec<i128>>,
+    F: Fn(&[i128]) -> bool + Sync,
+{
+    let mut space = space.into_iter().fuse();
+    loop {
+        let chunk: Vec<Vec<i128>> = space.by_ref().take(CHUNK).collect();
+        if chunk.is_empty() {
+            return None;
+        }
+        if let Some(p) = chunk.into_par_iter().find_first(|p| f(p)) {
+            return Some(p);
+        }
+    }
+}
+
+/// The candidate `f` scores highest, with its score. Candidates `f` returns
+/// `None` for are skipped. Ties go to the one `space` yields last. `space`
+/// is pulled a chunk at a time like in `find_by`, but it has to end.
+pub fn argmax_by<S, F>(space: S, f: F) -> Option<(Vec<i128>, i128)>
+where
+    S: IntoIterator<Item = Vec<i128>>,
+    F: Fn(&[i128]) -> Option<i128> + Sync,
+{
+    let mut space = space.into_iter().fuse();
+    let mut best: Option<(Vec<i128>, i128)> = None;
+    loop {
+        let chunk: Vec<Vec<i128>> = space.by_ref().take(CHUNK).collect();
+        if chunk.is_empty() {
+            return best;
+        }
+        let max = chunk
+            .into_par_iter()
+            .filter_map(|p| f(&p).map(|x| (p, x)))
+            .max_by_key(|(_, x)| *x);
+        match (&best, max) {
+            (Some((_, b)), Some((_, x))) if *b > x => {}
+            (_, Some(m)) => best = Some(m),
+            (_, None) => {}
+        }
+    }
+}
+
+/// Runs one program over many candidate parameters.
+pub struct Search {
+    program: Vec<i128>,
+    seed: Seed,
+}
+
+impl Search {
+    pub fn new(t: &[i128], seed: Seed) -> Search {
+        Search {
+            program: t.to_vec(),
+            seed,
+        }
+    }
+
+    /// Runs the program to completion with one candidate's parameters.
+    pub fn run(&self, params: &[i128]) -> Result<Run, IntcodeError> {
+        let mut program = Program::new(&self.program);
+        match &self.seed {
+            Seed::Patch(addrs) => {
+                for (a, x) in addrs.iter().zip(params) {
+                    program.set(*a, *x);
+                }
+            }
+            Seed::Inputs => program.inputs.extend(params),
+        }
+        let mut outputs = Vec::new();
+        while let StepOutcome::Output(o) = program.intcode()? {
+            outputs.push(o);
+        }
+        Ok(Run { program, outputs })
+    }
+
+    /// The first candidate whose run satisfies `goal`. Runs that fail never
+    /// match.
+    pub fn find<S, G>(&self, space: S, goal: G) -> Option<Vec<i128>>
+    where
+        S: IntoIterator<Item = Vec<i128>>,
+        G: Fn(&Run) -> bool + Sync,
+    {
+        find_by(space, |p| self.run(p).is_ok_and(|r| goal(&r)))
+    }
+
+    /// The candidate whose run scores highest. Runs that fail are skipped.
+    pub fn argmax<S, O>(&self, space: S, objective: O) -> Option<(Vec<i128>, i128)>
+    where
+        S: IntoIterator<Item = Vec<i128>>,
+        O: Fn(&Run) -> i128 + Sync,
+    {
+        argmax_by(space, |p| self.run(p).ok().map(|r| objective(&r)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spaces() {
+        assert_eq!(
+            vec![vec![0, 5], vec![0, 6], vec![1, 5], vec![1, 6]],
+            grid(&[0..=1, 5..=6])
+        );
+        assert_eq!(120, permutations(&[0, 1, 2, 3, 4]).len());
+        assert_eq!(vec![vec![1, 2], vec![2, 1]], permutations(&[1, 2]));
+    }
+
+    #[test]
+    fn test_lazy_space() {
+        // endless, and spanning several chunks before the match
+        let naturals = (0..).map(|x| vec![x]);
+        assert_eq!(Some(vec![5000]), find_by(naturals, |p| p[0] == 5000));
+
+        // ties across chunks still go to the last
+        let space = (0..3000).map(|x| vec![x]);
+        assert_eq!(
+            Some((vec![2999], 1)),
+            argmax_by(space, |p| Some((p[0] % 1000 > 990) as i128))
+        );
+
+        // itertools' permutations start over once they run out
+        let mut n = 0;
+        let cycling = || {
+            std::iter::from_fn(move || {
+                n = (n + 1) % 4;
+                (n > 0).then(|| vec![n])
+            })
+        };
+        assert_eq!(None, find_by(cycling(), |p| p[0] == 0));
+        assert_eq!(Some((vec![3], 3)), argmax_by(cycling(), |p| Some(p[0])));
+    }
+
+    #[test]
+    fn test_patch() {
+        // [0] = [noun] * [verb]
+        let t = [2, 0, 0, 0, 99, 6, 7, 8, 9];
+        let s = Search::new(&t, Seed::Patch(vec![1, 2]));
+
+        let found = s.find(grid(&[5..=8, 5..=8]), |r| r.program.get(0) == 72);
+        assert_eq!(Some(vec![7, 8]), found);
+        assert_eq!(
+            None,
+            s.find(grid(&[5..=6, 5..=6]), |r| r.program.get(0) == 72)
+        );
+    }
+
+    #[test]
+    fn test_inputs() {
+        // day7's first example as a single amplifier: 10 * signal + phase
+        let t = [
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        let s = Search::new(&t, Seed::Inputs);
+
+        let best = s.argmax(grid(&[0..=4, 0..=9]), |r| r.outputs[0]);
+        assert_eq!(Some((vec![4, 9], 94)), best);
+        // one input isn't enough, so every run fails
+        assert_eq!(None, s.argmax(grid(&[0..=4]), |r| r.outputs[0]));
+    }
+}
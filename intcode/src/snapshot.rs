@@ -0,0 +1,252 @@
+use std::io;
+use std::io::{Read, Write};
+
+use crate::{varint, Program};
+
+const MAGIC: &[u8; 4] = b"ICS1";
+
+/// Everything needed to resume a `Program` where it left off. Tracing and
+/// the limits are settings rather than state, so they aren't captured, and
+/// the cells counted against a cell limit start over on restore.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub memory: Vec<(i128, i128)>,
+    pub i: i128,
+    pub rb: i128,
+    pub inputs: Vec<i128>,
+    pub count: usize,
+    pub outputs: usize,
+    pub patched: Vec<String>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Snapshot {
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        varint::write(w, self.i)?;
+        varint::write(w, self.rb)?;
+        varint::write(w, self.count as i128)?;
+        varint::write(w, self.outputs as i128)?;
+        varint::write(w, self.inputs.len() as i128)?;
+        for x in &self.inputs {
+            varint::write(w, *x)?;
+        }
+        varint::write(w, self.patched.len() as i128)?;
+        for name in &self.patched {
+            varint::write(w, name.len() as i128)?;
+            w.write_all(name.as_bytes())?;
+        }
+        // Cells are sorted, so store the gap to the previous address.
+        let mut prev = 0;
+        for (a, v) in &self.memory {
+            let gap = a
+                .checked_sub(prev)
+                .ok_or_else(|| invalid("addresses too far apart"))?;
+            varint::write(w, gap)?;
+            varint::write(w, *v)?;
+            prev = *a;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Snapshot> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an Intcode snapshot"));
+        }
+        let i = varint::read_one(r)?;
+        let rb = varint::read_one(r)?;
+        let count = varint::read_size(r, "instruction count")?;
+        let outputs = varint::read_size(r, "output count")?;
+        let mut inputs = Vec::new();
+        for _ in 0..varint::read_size(r, "input count")? {
+            inputs.push(varint::read_one(r)?);
+        }
+        let mut patched = Vec::new();
+        for _ in 0..varint::read_size(r, "patch count")? {
+            // a corrupt length runs out of input rather than memory
+            let len = varint::read_size(r, "patch name length")? as u64;
+            let mut name = Vec::new();
+            if r.by_ref().take(len).read_to_end(&mut name)? as u64 != len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated patch name",
+                ));
+            }
+            let name = String::from_utf8(name).map_err(|_| invalid("patch name isn't UTF-8"))?;
+            patched.push(name);
+        }
+        let mut memory = Vec::new();
+        let mut prev: i128 = 0;
+        while let Some(gap) = varint::read(r)? {
+            prev = match prev.checked_add(gap) {
+                Some(a) => a,
+                None => return Err(invalid("address out of range")),
+            };
+            memory.push((prev, varint::read_one(r)?));
+        }
+
+        Ok(Snapshot {
+            memory,
+            i,
+            rb,
+            inputs,
+            count,
+            outputs,
+            patched,
+        })
+    }
+}
+
+impl Program {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.cells(),
+            i: self.i,
+            rb: self.rb,
+            inputs: self.inputs.iter().copied().collect(),
+            count: self.count,
+            outputs: self.outputs,
+            patched: self.patched.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, s: &Snapshot) {
+        self.clear();
+        self.touched.clear();
+        for (a, v) in &s.memory {
+            self.set(*a, *v);
+        }
+        self.i = s.i;
+        self.rb = s.rb;
+        self.inputs = s.inputs.iter().copied().collect();
+        self.count = s.count;
+        self.outputs = s.outputs;
+        self.patched = s.patched.clone();
+    }
+}
+
+impl From<&Snapshot> for Program {
+    fn from(s: &Snapshot) -> Program {
+        let mut p = Program::new(&[]);
+        p.restore(s);
+        p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::QUINE;
+    use crate::{IntcodeError, State};
+
+    fn rest(p: &mut Program) -> Vec<i128> {
+        let mut out = Vec::new();
+        while let State::Output(o) = p.run().unwrap() {
+            out.push(o);
+        }
+        out
+    }
+
+    #[test]
+    fn test_fork() {
+        let mut p = Program::new(&QUINE);
+        for _ in 0..5 {
+            p.run().unwrap();
+        }
+        let s = p.snapshot();
+
+        let mut fork = Program::from(&s);
+        assert_eq!(QUINE[5..].to_vec(), rest(&mut fork));
+        assert_eq!(QUINE[5..].to_vec(), rest(&mut p));
+
+        p.restore(&s);
+        assert_eq!(QUINE[5..].to_vec(), rest(&mut p));
+    }
+
+    #[test]
+    fn test_restore_limits() {
+        let mut p = Program::new(&QUINE);
+        let s = p.snapshot();
+        p.limits.outputs = Some(3);
+        p.limits.cells = Some(10);
+        let run = |p: &mut Program| {
+            let mut out = Vec::new();
+            loop {
+                match p.run() {
+                    Ok(State::Output(o)) => out.push(o),
+                    Err(e) => return (out, e),
+                    Ok(s) => panic!("stopped with {:?}", s),
+                }
+            }
+        };
+        let (out, e) = run(&mut p);
+        assert_eq!(QUINE[..3].to_vec(), out);
+        assert!(matches!(e, IntcodeError::OutputLimitExceeded { .. }));
+
+        // the outputs and cells used before restoring don't count
+        p.restore(&s);
+        assert_eq!((0, 0), (p.vm_state().outputs, p.vm_state().cells));
+        assert_eq!(QUINE[..3].to_vec(), run(&mut p).0);
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let mut p = Program::new(&[3, 1000, 3, -1, 99]);
+        p.inputs.extend(&[7, 8, 9]);
+        p.patched.push("free play".to_string());
+        p.run().unwrap_err();
+        let s = p.snapshot();
+
+        let mut buf = Vec::new();
+        s.write(&mut buf).unwrap();
+        assert_eq!(s, Snapshot::read(&mut &buf[..]).unwrap());
+        assert_eq!(vec![8, 9], s.inputs);
+        assert!(s.memory.contains(&(1000, 7)));
+
+        let mut q = Program::new(&[]);
+        q.patched.push("alarm".to_string());
+        q.restore(&s);
+        assert_eq!(vec!["free play".to_string()], q.patched);
+    }
+
+    #[test]
+    fn test_far_apart() {
+        let mut p = Program::new(&[]);
+        p.set(-1, 1);
+        p.set(i128::MAX, 2);
+
+        let e = p.snapshot().write(&mut Vec::new()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, e.kind());
+    }
+
+    #[test]
+    fn test_corrupt_files() {
+        let file = |xs: &[i128]| {
+            let mut buf = MAGIC.to_vec();
+            for x in xs {
+                varint::write(&mut buf, *x).unwrap();
+            }
+            Snapshot::read(&mut &buf[..]).map_err(|e| (e.kind(), e.to_string()))
+        };
+        let invalid = |msg: &str| Err((io::ErrorKind::InvalidData, msg.to_string()));
+
+        // i, rb, count, outputs, inputs, patches, then gaps and cells
+        assert!(file(&[0, 0, 5, 1, 0, 0, 3, 7]).is_ok());
+        assert_eq!(
+            invalid("bad instruction count -1"),
+            file(&[0, 0, -1, 0, 0, 0])
+        );
+        assert_eq!(invalid("bad output count -2"), file(&[0, 0, 0, -2, 0, 0]));
+        assert_eq!(invalid("bad input count -1"), file(&[0, 0, 0, 0, -1, 0]));
+        assert_eq!(invalid("bad patch count -3"), file(&[0, 0, 0, 0, 0, -3]));
+        assert_eq!(
+            invalid("address out of range"),
+            file(&[0, 0, 0, 0, 0, 0, i128::MAX, 1, 1, 1])
+        );
+    }
+}
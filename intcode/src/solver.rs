@@ -0,0 +1,405 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::process::{Command, Stdio};
+
+use crate::{Constraint, Expr, Rel, SymbolicError};
+
+// The most assignments `Linear` will try before giving up.
+const MAX_TRIES: u128 = 1 << 24;
+
+/// Constraints over named symbols, each limited to a range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub names: Vec<String>,
+    pub domains: Vec<RangeInclusive<i128>>,
+    pub constraints: Vec<Constraint>,
+}
+
+impl Problem {
+    /// Whether `values` meet every constraint and stay in their ranges.
+    pub fn check(&self, values: &[i128]) -> bool {
+        self.domains.iter().zip(values).all(|(d, x)| d.contains(x))
+            && self.constraints.iter().all(|c| c.holds(values))
+    }
+}
+
+// The inverse of odd `b` modulo 2^128. Every odd number is its own inverse
+// modulo 8, and each round of Newton's method doubles the bits that are
+// right.
+fn inverse(b: u128) -> u128 {
+    let mut x = b;
+    for _ in 0..6 {
+        x = x.wrapping_mul(2u128.wrapping_sub(b.wrapping_mul(x)));
+    }
+    x
+}
+
+// Every x in `d`, lowest first, with a * x + rest = 0 in wrapping
+// arithmetic. Writing a as 2^k times an odd b, there are none unless 2^k
+// divides rest, and otherwise they repeat every 2^(128 - k); the step is
+// `None` when there's only one.
+fn roots(a: i128, rest: i128, d: &RangeInclusive<i128>) -> impl Iterator<Item = i128> {
+    let k = a.trailing_zeros();
+    let step = 1u128.checked_shl(128 - k);
+    let (lo, span) = (*d.start(), d.end().wrapping_sub(*d.start()) as u128);
+    let first = if d.is_empty() || (rest as u128).trailing_zeros() < k {
+        None
+    } else {
+        let x = ((rest as u128) >> k)
+            .wrapping_neg()
+            .wrapping_mul(inverse((a as u128) >> k));
+        // how far past `lo` the first one is
+        let off = x.wrapping_sub(lo as u128) & step.map_or(u128::MAX, |s| s - 1);
+        Some(off).filter(|o| *o <= span)
+    };
+    std::iter::successors(first, move |o| o.checked_add(step?).filter(|o| *o <= span))
+        .map(move |o| lo.wrapping_add(o as i128))
+}
+
+pub trait Solver {
+    /// Values for every symbol that meet the problem's constraints, or
+    /// `None` if there aren't any.
+    fn solve(&self, p: &Problem) -> Result<Option<Vec<i128>>, SymbolicError>;
+}
+
+/// The built-in solver. It solves one linear equation exactly for one of
+/// its symbols, in the same wrapping arithmetic as `Expr::eval`, and tries
+/// every combination of the other symbols that appear, so day2's two
+/// symbols take 100 tries rather than 10,000. Without a linear equation to
+/// use, every combination is tried.
+pub struct Linear;
+
+impl Solver for Linear {
+    fn solve(&self, p: &Problem) -> Result<Option<Vec<i128>>, SymbolicError> {
+        let pivot = p
+            .constraints
+            .iter()
+            .filter(|c| c.rel == Rel::Eq)
+            .filter_map(|c| {
+                let eq = c.lhs.sub(&c.rhs);
+                let (n, a) = eq.linear()?.into_iter().find(|(_, a)| *a != 0)?;
+                Some((n, a, eq))
+            })
+            .next();
+        let used: BTreeSet<usize> = p
+            .constraints
+            .iter()
+            .flat_map(|c| c.lhs.symbols().into_iter().chain(c.rhs.symbols()))
+            .collect();
+        let free: Vec<usize> = used
+            .into_iter()
+            .filter(|n| pivot.as_ref().map(|p| p.0) != Some(*n))
+            .collect();
+
+        let tries = free.iter().try_fold(1u128, |acc, n| {
+            let d = &p.domains[*n];
+            // the whole of i128 has one value too many for a u128
+            let size = if d.is_empty() {
+                Some(0)
+            } else {
+                (d.end().wrapping_sub(*d.start()) as u128).checked_add(1)
+            };
+            size.and_then(|s| acc.checked_mul(s))
+                .filter(|t| *t <= MAX_TRIES)
+        });
+        // an even coefficient can leave more than one root in the domain
+        let tries = tries.and_then(|t| match &pivot {
+            Some((n, a, _)) => {
+                let d = &p.domains[*n];
+                let span = d.end().wrapping_sub(*d.start()) as u128;
+                let roots = 1u128
+                    .checked_shl(128 - a.trailing_zeros())
+                    .map_or(1, |s| span / s + 1);
+                t.checked_mul(roots).filter(|t| *t <= MAX_TRIES)
+            }
+            None => Some(t),
+        });
+        match tries {
+            None => return Err(SymbolicError::Solver("search space too large".to_string())),
+            Some(0) => return Ok(None),
+            Some(_) => {}
+        }
+
+        let mut values: Vec<i128> = p.domains.iter().map(|d| *d.start()).collect();
+        loop {
+            if let Some((n, a, ref eq)) = pivot {
+                values[n] = 0;
+                let rest = eq.eval(&values);
+                for x in roots(a, rest, &p.domains[n]) {
+                    values[n] = x;
+                    if p.check(&values) {
+                        return Ok(Some(values));
+                    }
+                }
+            } else if p.check(&values) {
+                return Ok(Some(values));
+            }
+
+            // the next combination, like an odometer
+            let mut carry = true;
+            for n in free.iter().rev() {
+                let d = &p.domains[*n];
+                if values[*n] < *d.end() {
+                    values[*n] += 1;
+                    carry = false;
+                    break;
+                }
+                values[*n] = *d.start();
+            }
+            if carry {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Hands the problem to an external SMT solver that reads SMT-LIB on
+/// stdin, such as `z3 -in`. Symbol names have to be valid SMT-LIB symbols.
+pub struct Smt {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl Smt {
+    pub fn new(command: &str, args: &[&str]) -> Smt {
+        Smt {
+            command: command.to_string(),
+            args: args.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// The problem as an SMT-LIB script asking for a model. Symbols are
+    /// 128-bit vectors, so arithmetic wraps the way `Expr::eval` does.
+    pub fn script(p: &Problem) -> String {
+        // the bits of an i128 are its two's complement
+        let num = |x: i128| format!("(_ bv{} 128)", x as u128);
+        let mut s = String::from("(set-logic QF_BV)\n");
+        for (name, d) in p.names.iter().zip(&p.domains) {
+            s += &format!("(declare-const {} (_ BitVec 128))\n", name);
+            s += &format!(
+                "(assert (and (bvsge {} {}) (bvsle {} {})))\n",
+                name,
+                num(*d.start()),
+                name,
+                num(*d.end())
+            );
+        }
+        let expr = |e: &Expr| {
+            if let Some(c) = e.as_constant() {
+                return num(c);
+            }
+            e.render(
+                |n| p.names[n].clone(),
+                |t| match t.len() {
+                    1 => t[0].clone(),
+                    _ => format!("(bvadd {})", t.join(" ")),
+                },
+                |f| {
+                    let f: Vec<String> = f
+                        .into_iter()
+                        .map(|x| x.parse().map(num).unwrap_or(x))
+                        .collect();
+                    match f.len() {
+                        1 => f[0].clone(),
+                        _ => format!("(bvmul {})", f.join(" ")),
+                    }
+                },
+            )
+        };
+        for c in &p.constraints {
+            let (a, b) = (expr(&c.lhs), expr(&c.rhs));
+            let assertion = match c.rel {
+                Rel::Eq => format!("(= {} {})", a, b),
+                Rel::Ne => format!("(not (= {} {}))", a, b),
+                Rel::Lt => format!("(bvslt {} {})", a, b),
+                Rel::Ge => format!("(bvsge {} {})", a, b),
+            };
+            s += &format!("(assert {})\n", assertion);
+        }
+        s += "(check-sat)\n";
+        if !p.names.is_empty() {
+            s += &format!("(get-value ({}))\n", p.names.join(" "));
+        }
+        s
+    }
+}
+
+// Reads the answer to `script`: `sat` and the symbols' values, or `unsat`.
+// Values can be bit vectors, in hex, binary or `(_ bvN 128)`, or integers.
+fn model(out: &str, names: &[String]) -> Result<Option<Vec<i128>>, SymbolicError> {
+    let bad = |msg: &str| SymbolicError::Solver(msg.to_string());
+    let spaced = out.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    match tokens.first() {
+        Some(&"sat") => {}
+        Some(&"unsat") => return Ok(None),
+        _ => return Err(bad(out.trim())),
+    }
+
+    let bits = |x: &str, radix| u128::from_str_radix(x, radix).ok().map(|x| x as i128);
+    let mut values = Vec::new();
+    for name in names {
+        let n = match tokens.iter().position(|t| t == name) {
+            Some(n) => n,
+            None => return Err(bad(&format!("no value for {}", name))),
+        };
+        let x = match tokens[n + 1..] {
+            ["(", "_", x, "128", ")", ..] => x.strip_prefix("bv").and_then(|x| bits(x, 10)),
+            ["(", "-", x, ")", ..] => format!("-{}", x).parse().ok(),
+            [x, ..] => match (x.strip_prefix("#x"), x.strip_prefix("#b")) {
+                (Some(x), _) if x.len() == 32 => bits(x, 16),
+                (_, Some(x)) if x.len() == 128 => bits(x, 2),
+                _ => x.parse().ok(),
+            },
+            [] => return Err(bad(&format!("no value for {}", name))),
+        };
+        values.push(x.ok_or_else(|| bad(&format!("bad value for {}", name)))?);
+    }
+    Ok(Some(values))
+}
+
+impl Solver for Smt {
+    fn solve(&self, p: &Problem) -> Result<Option<Vec<i128>>, SymbolicError> {
+        let fail = |e: std::io::Error| SymbolicError::Solver(format!("{}: {}", self.command, e));
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(fail)?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(Smt::script(p).as_bytes())
+            .map_err(fail)?;
+        let out = child.wait_with_output().map_err(fail)?;
+        match model(&String::from_utf8_lossy(&out.stdout), &p.names)? {
+            Some(values) if !p.check(&values) => Err(SymbolicError::Solver(
+                "model doesn't meet the constraints".to_string(),
+            )),
+            m => Ok(m),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem() -> Problem {
+        // 100 * a + b = 2347 with b at least 1
+        let (a, b) = (Expr::symbol(0), Expr::symbol(1));
+        let sum = a.mul(&Expr::constant(100)).add(&b);
+        Problem {
+            names: vec!["a".to_string(), "b".to_string()],
+            domains: vec![0..=99, 0..=99],
+            constraints: vec![
+                Constraint {
+                    lhs: sum,
+                    rel: Rel::Eq,
+                    rhs: Expr::constant(2347),
+                },
+                Constraint {
+                    lhs: b,
+                    rel: Rel::Ge,
+                    rhs: Expr::constant(1),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_linear() {
+        assert_eq!(Ok(Some(vec![23, 47])), Linear.solve(&problem()));
+
+        let mut p = problem();
+        p.domains[1] = 0..=40;
+        assert_eq!(Ok(None), Linear.solve(&p));
+    }
+
+    #[test]
+    fn test_linear_extremes() {
+        // MIN - x = 0, solved for x by dividing MIN by -1
+        let x = Expr::symbol(0);
+        let mut p = Problem {
+            names: vec!["x".to_string()],
+            domains: vec![i128::MIN..=i128::MAX],
+            constraints: vec![Constraint {
+                lhs: Expr::constant(i128::MIN).sub(&x),
+                rel: Rel::Eq,
+                rhs: Expr::constant(0),
+            }],
+        };
+        assert_eq!(Ok(Some(vec![i128::MIN])), Linear.solve(&p));
+
+        // 3 * x = 1 only has a root once it wraps, and 2 * x = 0 has two,
+        // 0 and MIN
+        p.constraints[0].lhs = Expr::constant(3).mul(&x);
+        p.constraints[0].rhs = Expr::constant(1);
+        let root = Linear.solve(&p).unwrap().unwrap();
+        assert_eq!(1, 3i128.wrapping_mul(root[0]));
+        p.constraints[0].lhs = Expr::constant(2).mul(&x);
+        p.constraints[0].rhs = Expr::constant(0);
+        p.domains[0] = i128::MIN..=-1;
+        assert_eq!(Ok(Some(vec![i128::MIN])), Linear.solve(&p));
+
+        // nothing to pivot on, and a domain too big to count in a u128
+        p.domains[0] = i128::MIN..=i128::MAX;
+        p.constraints[0].rel = Rel::Lt;
+        assert!(matches!(Linear.solve(&p), Err(SymbolicError::Solver(_))));
+    }
+
+    #[test]
+    fn test_script() {
+        let s = Smt::script(&problem());
+
+        assert!(s.contains("(declare-const a (_ BitVec 128))"));
+        assert!(s.contains("(assert (= (bvadd b (bvmul (_ bv100 128) a)) (_ bv2347 128)))"));
+        assert!(s.contains("(assert (bvsge b (_ bv1 128)))"));
+        assert!(s.ends_with("(check-sat)\n(get-value (a b))\n"));
+    }
+
+    #[test]
+    fn test_model() {
+        let names = vec!["a".to_string(), "b".to_string()];
+
+        assert_eq!(
+            Ok(Some(vec![23, -47])),
+            model("sat\n((a 23)\n (b (- 47)))\n", &names)
+        );
+        assert_eq!(Ok(None), model("unsat\n", &names));
+
+        // i128::MIN as an integer and as a bit vector
+        let min = "170141183460469231731687303715884105728";
+        let out = format!("sat\n((a (- {}))\n (b (_ bv{} 128)))\n", min, min);
+        assert_eq!(Ok(Some(vec![i128::MIN, i128::MIN])), model(&out, &names));
+        let out = format!(
+            "sat\n((a #x{})\n (b #b{}))\n",
+            "f".repeat(32),
+            "0".repeat(128)
+        );
+        assert_eq!(Ok(Some(vec![-1, 0])), model(&out, &names));
+        assert!(model("(error \"oops\")", &names).is_err());
+    }
+
+    #[test]
+    fn test_wrong_model() {
+        // a solver whose answer doesn't meet 100 * a + b = 2347
+        let smt = Smt::new("sh", &["-c", "cat >/dev/null; echo sat '((a 0) (b 1))'"]);
+        assert!(matches!(
+            smt.solve(&problem()),
+            Err(SymbolicError::Solver(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing_binary() {
+        let smt = Smt::new("/nonexistent/solver", &["-in"]);
+        assert!(matches!(
+            smt.solve(&problem()),
+            Err(SymbolicError::Solver(_))
+        ));
+    }
+}
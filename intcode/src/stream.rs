@@ -0,0 +1,119 @@
+use std::error::Error;
+use std::fmt;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use crate::{IntcodeError, Program, State};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsyncError<E> {
+    Intcode(IntcodeError),
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for AsyncError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsyncError::Intcode(e) => write!(f, "{}", e),
+            AsyncError::Sink(e) => write!(f, "output: {}", e),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for AsyncError<E> {}
+
+impl<E> From<IntcodeError> for AsyncError<E> {
+    fn from(e: IntcodeError) -> AsyncError<E> {
+        AsyncError::Intcode(e)
+    }
+}
+
+impl Program {
+    /// Runs until the program halts, awaiting `input` whenever the queue is
+    /// empty and sending every output to `output`. The input stream ending
+    /// early is `InputExhausted`. `output` is flushed but not closed, so
+    /// several programs can share one sink.
+    ///
+    /// Between reads and writes the program runs without yielding, so a
+    /// long computation holds up the executor's other tasks.
+    pub async fn run_async<I, O>(
+        &mut self,
+        mut input: I,
+        mut output: O,
+    ) -> Result<(), AsyncError<O::Error>>
+    where
+        I: Stream<Item = i128> + Unpin,
+        O: Sink<i128> + Unpin,
+    {
+        loop {
+            match self.run()? {
+                State::Output(o) => output.feed(o).await.map_err(AsyncError::Sink)?,
+                State::NeedsInput => {
+                    output.flush().await.map_err(AsyncError::Sink)?;
+                    match input.next().await {
+                        Some(x) => self.inputs.push_back(x),
+                        None => return Err(IntcodeError::InputExhausted { ip: self.i }.into()),
+                    }
+                }
+                State::Halted => return output.flush().await.map_err(AsyncError::Sink),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::FEEDBACK;
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::future::join_all;
+    use futures::stream;
+
+    #[test]
+    fn test_echo() {
+        // day9's relative-mode echo
+        let mut p = Program::new(&[109, 1, 203, 2, 204, 2, 99]);
+        let mut out = Vec::new();
+        block_on(p.run_async(stream::iter(vec![42]), &mut out)).unwrap();
+
+        assert_eq!(vec![42], out);
+    }
+
+    #[test]
+    fn test_input_ends() {
+        let mut p = Program::new(&[3, 5, 4, 5, 99, 0]);
+        let mut out = Vec::new();
+        let r = block_on(p.run_async(stream::empty(), &mut out));
+
+        assert_eq!(
+            Err(AsyncError::Intcode(IntcodeError::InputExhausted { ip: 0 })),
+            r
+        );
+    }
+
+    #[test]
+    fn test_feedback_loop() {
+        // day7's feedback loop, all five amplifiers on one thread
+        let phases = [9, 8, 7, 6, 5];
+        let mut programs: Vec<Program> = phases.iter().map(|_| Program::new(&FEEDBACK)).collect();
+        let (mut senders, mut receivers): (Vec<_>, Vec<_>) =
+            phases.iter().map(|_| mpsc::unbounded()).unzip();
+        for (tx, x) in senders.iter_mut().zip(&phases) {
+            tx.unbounded_send(*x).unwrap();
+        }
+        senders[0].unbounded_send(0).unwrap();
+        senders.rotate_left(1);
+
+        let runs = programs
+            .iter_mut()
+            .zip(receivers.iter_mut())
+            .zip(senders.iter_mut())
+            .map(|((p, rx), tx)| p.run_async(rx, tx));
+        for r in block_on(join_all(runs)) {
+            r.unwrap();
+        }
+
+        assert_eq!(139629729, receivers[0].try_recv().unwrap());
+    }
+}
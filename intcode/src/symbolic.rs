@@ -0,0 +1,781 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::instruction::{Mode, Op};
+use crate::{IntcodeError, Linear, Problem, Solver, VmState};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    Intcode(IntcodeError),
+    /// A write whose address depends on a symbol.
+    SymbolicAddress {
+        ip: i128,
+    },
+    /// An opcode, jump target or relative base that depends on a symbol, or
+    /// a branch on a value read through a symbolic address.
+    SymbolicControl {
+        ip: i128,
+    },
+    TooManyPaths {
+        limit: usize,
+    },
+    Solver(String),
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolicError::Intcode(e) => write!(f, "{}", e),
+            SymbolicError::SymbolicAddress { ip } => {
+                write!(f, "write through a symbolic address at {}", ip)
+            }
+            SymbolicError::SymbolicControl { ip } => {
+                write!(f, "control flow depends on an unresolved value at {}", ip)
+            }
+            SymbolicError::TooManyPaths { limit } => write!(f, "more than {} paths", limit),
+            SymbolicError::Solver(msg) => write!(f, "solver: {}", msg),
+        }
+    }
+}
+
+impl Error for SymbolicError {}
+
+impl From<IntcodeError> for SymbolicError {
+    fn from(e: IntcodeError) -> SymbolicError {
+        SymbolicError::Intcode(e)
+    }
+}
+
+/// A polynomial in the symbols, with integer coefficients. Arithmetic wraps
+/// the same way `Program` does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expr {
+    // each monomial, as the sorted symbols multiplied together, with its
+    // coefficient; zero coefficients are never stored
+    terms: BTreeMap<Vec<usize>, i128>,
+}
+
+impl Expr {
+    pub fn constant(c: i128) -> Expr {
+        let mut terms = BTreeMap::new();
+        if c != 0 {
+            terms.insert(Vec::new(), c);
+        }
+        Expr { terms }
+    }
+
+    pub fn symbol(n: usize) -> Expr {
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![n], 1);
+        Expr { terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i128> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((m, c)) if m.is_empty() && self.terms.len() == 1 => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn term(&mut self, m: Vec<usize>, c: i128) {
+        let x = self.terms.entry(m.clone()).or_insert(0);
+        *x = x.wrapping_add(c);
+        if *x == 0 {
+            self.terms.remove(&m);
+        }
+    }
+
+    pub fn add(&self, o: &Expr) -> Expr {
+        let mut e = self.clone();
+        for (m, c) in &o.terms {
+            e.term(m.clone(), *c);
+        }
+        e
+    }
+
+    pub fn sub(&self, o: &Expr) -> Expr {
+        self.add(&o.mul(&Expr::constant(-1)))
+    }
+
+    pub fn mul(&self, o: &Expr) -> Expr {
+        let mut e = Expr::default();
+        for (a, x) in &self.terms {
+            for (b, y) in &o.terms {
+                let mut m: Vec<usize> = a.iter().chain(b).copied().collect();
+                m.sort_unstable();
+                e.term(m, x.wrapping_mul(*y));
+            }
+        }
+        e
+    }
+
+    /// The value with each symbol `n` set to `values[n]`.
+    pub fn eval(&self, values: &[i128]) -> i128 {
+        self.terms.iter().fold(0i128, |acc, (m, c)| {
+            let t = m.iter().fold(*c, |t, n| t.wrapping_mul(values[*n]));
+            acc.wrapping_add(t)
+        })
+    }
+
+    pub fn symbols(&self) -> BTreeSet<usize> {
+        self.terms.keys().flatten().copied().collect()
+    }
+
+    /// The coefficient of each symbol, if no term has more than one.
+    pub fn linear(&self) -> Option<BTreeMap<usize, i128>> {
+        let mut coeffs = BTreeMap::new();
+        for (m, c) in &self.terms {
+            match m.len() {
+                0 => {}
+                1 => {
+                    coeffs.insert(m[0], *c);
+                }
+                _ => return None,
+            }
+        }
+        Some(coeffs)
+    }
+
+    // Renders the polynomial with each symbol spelled by `name`, using `sum`
+    // and `product` to join terms and factors.
+    pub(crate) fn render<F, S, P>(&self, name: F, sum: S, product: P) -> String
+    where
+        F: Fn(usize) -> String,
+        S: Fn(Vec<String>) -> String,
+        P: Fn(Vec<String>) -> String,
+    {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .rev()
+            .map(|(m, c)| {
+                let mut factors: Vec<String> = m.iter().map(|n| name(*n)).collect();
+                if *c != 1 || m.is_empty() {
+                    factors.insert(0, c.to_string());
+                }
+                product(factors)
+            })
+            .collect();
+        if terms.is_empty() {
+            "0".to_string()
+        } else {
+            sum(terms)
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = self.render(|n| format!("x{}", n), |t| t.join(" + "), |t| t.join("*"));
+        write!(f, "{}", s)
+    }
+}
+
+/// How the two sides of a constraint compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rel {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+}
+
+/// Two expressions and how they compare. Each side wraps like `Program`
+/// does, but the comparison doesn't, so `lt` is never decided by the sign
+/// of a difference that overflowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub lhs: Expr,
+    pub rel: Rel,
+    pub rhs: Expr,
+}
+
+impl Constraint {
+    pub fn holds(&self, values: &[i128]) -> bool {
+        let (a, b) = (self.lhs.eval(values), self.rhs.eval(values));
+        match self.rel {
+            Rel::Eq => a == b,
+            Rel::Ne => a != b,
+            Rel::Lt => a < b,
+            Rel::Ge => a >= b,
+        }
+    }
+
+    // Whether it holds, if that doesn't depend on the symbols. Sides that
+    // differ by a constant are only equal or not, since wrapping can't
+    // change that.
+    fn decided(&self) -> Option<bool> {
+        match self.rel {
+            Rel::Eq | Rel::Ne => {
+                let d = self.lhs.sub(&self.rhs).as_constant()?;
+                Some((d == 0) == (self.rel == Rel::Eq))
+            }
+            Rel::Lt | Rel::Ge => {
+                self.lhs.as_constant()?;
+                self.rhs.as_constant()?;
+                Some(self.holds(&[]))
+            }
+        }
+    }
+
+    fn negate(&self) -> Constraint {
+        let rel = match self.rel {
+            Rel::Eq => Rel::Ne,
+            Rel::Ne => Rel::Eq,
+            Rel::Lt => Rel::Ge,
+            Rel::Ge => Rel::Lt,
+        };
+        Constraint {
+            lhs: self.lhs.clone(),
+            rel,
+            rhs: self.rhs.clone(),
+        }
+    }
+}
+
+/// How a path through the program ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum End {
+    Halted,
+    NeedsInput,
+    Failed(SymbolicError),
+}
+
+/// One way through the program, with the constraints the symbols have to
+/// meet to take it. Values read through a symbolic address aren't tracked
+/// and show up as `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    memory: HashMap<i128, Option<Expr>>,
+    pub i: i128,
+    pub rb: i128,
+    inputs: VecDeque<Expr>,
+    pub outputs: Vec<Option<Expr>>,
+    pub constraints: Vec<Constraint>,
+    pub count: usize,
+    pub end: Option<End>,
+}
+
+impl Path {
+    pub fn get(&self, a: i128) -> Option<Expr> {
+        match self.memory.get(&a) {
+            Some(x) => x.clone(),
+            None => Some(Expr::default()),
+        }
+    }
+
+    // The operand in cell `a` as an address, if it's known.
+    fn address(&self, a: i128) -> Option<i128> {
+        self.get(a)?.as_constant()
+    }
+
+    fn load(&self, a: i128) -> Result<Option<Expr>, SymbolicError> {
+        if a < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.i,
+                addr: a,
+            }
+            .into());
+        }
+        Ok(self.get(a))
+    }
+
+    // `a` relative to the base, failing like `Program` does if that
+    // overflows.
+    fn offset(&self, a: i128) -> Result<i128, SymbolicError> {
+        match self.rb.checked_add(a) {
+            Some(a) => Ok(a),
+            None => Err(IntcodeError::Overflow { ip: self.i }.into()),
+        }
+    }
+
+    fn param(&self, modes: &[Mode; 3], n: usize) -> Result<Option<Expr>, SymbolicError> {
+        let at = self.i + 1 + n as i128;
+        match modes[n] {
+            Mode::Immediate => Ok(self.get(at)),
+            Mode::Position => match self.address(at) {
+                Some(a) => self.load(a),
+                None => Ok(None),
+            },
+            Mode::Relative => match self.address(at) {
+                Some(a) => self.load(self.offset(a)?),
+                None => Ok(None),
+            },
+        }
+    }
+
+    fn dest(&self, modes: &[Mode; 3], n: usize) -> Result<i128, SymbolicError> {
+        let a = match self.address(self.i + 1 + n as i128) {
+            Some(a) => a,
+            None => return Err(SymbolicError::SymbolicAddress { ip: self.i }),
+        };
+        let a = match modes[n] {
+            Mode::Relative => self.offset(a)?,
+            _ => a,
+        };
+        if a < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.i,
+                addr: a,
+            }
+            .into());
+        }
+        Ok(a)
+    }
+
+    fn known(&self, x: Option<Expr>) -> Result<Expr, SymbolicError> {
+        x.ok_or(SymbolicError::SymbolicControl { ip: self.i })
+    }
+
+    // Executes one instruction. When its outcome depends on the symbols, this
+    // path takes one side and the other side is returned as a new path.
+    fn step(&mut self) -> Result<Option<Path>, SymbolicError> {
+        let ip = self.i;
+        if ip < 0 {
+            return Err(IntcodeError::NegativeAddress { ip, addr: ip }.into());
+        }
+        let t = match self.get(ip).and_then(|x| x.as_constant()) {
+            Some(t) => t,
+            None => return Err(SymbolicError::SymbolicControl { ip }),
+        };
+        let op = match Op::from_code(t % 100) {
+            Some(op) => op,
+            None => return Err(IntcodeError::UnknownOpcode { ip, value: t }.into()),
+        };
+        let mut modes = [Mode::Position; 3];
+        let mut div = 100;
+        for (n, mode) in modes.iter_mut().enumerate().take(op.params()) {
+            let m = (t / div) % 10;
+            *mode = match Mode::from_code(m) {
+                Some(Mode::Immediate) if op.dest() == Some(n) => None,
+                mode => mode,
+            }
+            .ok_or(IntcodeError::BadMode { ip, mode: m })?;
+            div *= 10;
+        }
+        if ip.checked_add(op.params() as i128).is_none() {
+            return Err(IntcodeError::Overflow { ip }.into());
+        }
+        // only an error once something falls through to it, as in `Program`
+        let next = ip
+            .checked_add(1 + op.params() as i128)
+            .ok_or(IntcodeError::Overflow { ip });
+
+        let mut fork = None;
+        match op {
+            Op::Add | Op::Mul => {
+                let a = self.param(&modes, 0)?;
+                let b = self.param(&modes, 1)?;
+                let c = self.dest(&modes, 2)?;
+                let x = match (a, b) {
+                    (Some(a), Some(b)) if op == Op::Add => Some(a.add(&b)),
+                    (Some(a), Some(b)) => Some(a.mul(&b)),
+                    _ => None,
+                };
+                self.i = next?;
+                self.memory.insert(c, x);
+            }
+            Op::Lt | Op::Eq => {
+                let a = self.param(&modes, 0)?;
+                let b = self.param(&modes, 1)?;
+                let c = self.dest(&modes, 2)?;
+                let rel = if op == Op::Lt { Rel::Lt } else { Rel::Eq };
+                let test = Constraint {
+                    lhs: self.known(a)?,
+                    rel,
+                    rhs: self.known(b)?,
+                };
+                self.i = next?;
+                match test.decided() {
+                    Some(x) => {
+                        self.memory.insert(c, Some(Expr::constant(x as i128)));
+                    }
+                    None => {
+                        let mut other = self.clone();
+                        other.memory.insert(c, Some(Expr::constant(0)));
+                        other.constraints.push(test.negate());
+                        self.memory.insert(c, Some(Expr::constant(1)));
+                        self.constraints.push(test);
+                        fork = Some(other);
+                    }
+                }
+            }
+            Op::Jt | Op::Jf => {
+                let a = self.param(&modes, 0)?;
+                let a = self.known(a)?;
+                let target = self.param(&modes, 1)?;
+                let target = match target.and_then(|x| x.as_constant()) {
+                    Some(x) => x,
+                    None => return Err(SymbolicError::SymbolicControl { ip }),
+                };
+                let rel = if op == Op::Jt { Rel::Ne } else { Rel::Eq };
+                let jump = Constraint {
+                    lhs: a,
+                    rel,
+                    rhs: Expr::constant(0),
+                };
+                match jump.decided() {
+                    Some(taken) => self.i = if taken { target } else { next? },
+                    None => {
+                        let mut other = self.clone();
+                        match next {
+                            Ok(next) => other.i = next,
+                            Err(e) => other.end = Some(End::Failed(e.into())),
+                        }
+                        other.constraints.push(jump.negate());
+                        self.i = target;
+                        self.constraints.push(jump);
+                        fork = Some(other);
+                    }
+                }
+            }
+            Op::In => {
+                let c = self.dest(&modes, 0)?;
+                let next = next?;
+                match self.inputs.pop_front() {
+                    Some(x) => {
+                        self.memory.insert(c, Some(x));
+                        self.i = next;
+                    }
+                    None => {
+                        self.end = Some(End::NeedsInput);
+                        return Ok(None);
+                    }
+                }
+            }
+            Op::Out => {
+                let a = self.param(&modes, 0)?;
+                self.i = next?;
+                self.outputs.push(a);
+            }
+            Op::Arb => {
+                let a = self.param(&modes, 0)?;
+                let rb = match a.and_then(|x| x.as_constant()) {
+                    Some(x) => self.offset(x)?,
+                    None => return Err(SymbolicError::SymbolicControl { ip }),
+                };
+                self.i = next?;
+                self.rb = rb;
+            }
+            Op::Hlt => {
+                self.end = Some(End::Halted);
+                return Ok(None);
+            }
+        }
+        self.count += 1;
+        if let Some(f) = fork.as_mut().filter(|f| f.end.is_none()) {
+            f.count += 1;
+        }
+        Ok(fork)
+    }
+}
+
+/// What a solution has to achieve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// The cell at the address holds the value once the program halts.
+    Cell(i128, i128),
+    /// The `n`th output is the value.
+    Output(usize, i128),
+}
+
+/// Runs a program with some of its memory or input left as symbols,
+/// following every path the symbols could take, then solves for symbol
+/// values that meet a goal.
+///
+/// Only opcodes, jump targets, write addresses and the relative base have
+/// to stay concrete. A read through a symbolic address gives a value that
+/// isn't tracked, which is fine as long as it's overwritten or never
+/// branched on, like day2's first instruction.
+pub struct Symbolic {
+    /// Instructions allowed on any one path.
+    pub limit: usize,
+    /// Paths allowed in total before giving up.
+    pub max_paths: usize,
+    names: Vec<String>,
+    domains: Vec<RangeInclusive<i128>>,
+    start: Path,
+}
+
+impl Symbolic {
+    pub fn new(t: &[i128]) -> Symbolic {
+        let memory = t
+            .iter()
+            .enumerate()
+            .map(|(a, x)| (a as i128, Some(Expr::constant(*x))))
+            .collect();
+        Symbolic {
+            limit: 1_000_000,
+            max_paths: 1024,
+            names: Vec::new(),
+            domains: Vec::new(),
+            start: Path {
+                memory,
+                i: 0,
+                rb: 0,
+                inputs: VecDeque::new(),
+                outputs: Vec::new(),
+                constraints: Vec::new(),
+                count: 0,
+                end: None,
+            },
+        }
+    }
+
+    /// A new symbol that can take any value in `domain`.
+    pub fn symbol(&mut self, name: &str, domain: RangeInclusive<i128>) -> Expr {
+        self.names.push(name.to_string());
+        self.domains.push(domain);
+        Expr::symbol(self.names.len() - 1)
+    }
+
+    pub fn set(&mut self, a: i128, x: Expr) {
+        self.start.memory.insert(a, Some(x));
+    }
+
+    pub fn input(&mut self, x: Expr) {
+        self.start.inputs.push_back(x);
+    }
+
+    // Whether some values in the symbols' domains take path `p`. The check
+    // uses the built-in solver, and paths too big for it to rule out are
+    // kept.
+    fn feasible(&self, p: &Path) -> bool {
+        !matches!(Linear.solve(&self.problem(p.constraints.clone())), Ok(None))
+    }
+
+    /// Every path through the program the symbols' domains allow, in no
+    /// particular order.
+    pub fn explore(&self) -> Result<Vec<Path>, SymbolicError> {
+        let mut work = vec![self.start.clone()];
+        let mut done = Vec::new();
+        'paths: while let Some(mut p) = work.pop() {
+            while p.end.is_none() {
+                if p.count >= self.limit {
+                    let e = IntcodeError::InstructionLimitExceeded {
+                        limit: self.limit,
+                        state: Box::new(VmState {
+                            ip: p.i,
+                            rb: p.rb,
+                            instructions: p.count,
+                            cells: 0,
+                            outputs: p.outputs.len(),
+                        }),
+                    };
+                    p.end = Some(End::Failed(e.into()));
+                    break;
+                }
+                match p.step() {
+                    Ok(Some(fork)) => {
+                        if self.feasible(&fork) {
+                            work.push(fork);
+                        }
+                        if !self.feasible(&p) {
+                            continue 'paths;
+                        }
+                        if work.len() + done.len() + 1 > self.max_paths {
+                            return Err(SymbolicError::TooManyPaths {
+                                limit: self.max_paths,
+                            });
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => p.end = Some(End::Failed(e)),
+                }
+            }
+            done.push(p);
+        }
+        Ok(done)
+    }
+
+    /// The constraints over this engine's symbols as a problem for a
+    /// solver.
+    pub fn problem(&self, constraints: Vec<Constraint>) -> Problem {
+        Problem {
+            names: self.names.clone(),
+            domains: self.domains.clone(),
+            constraints,
+        }
+    }
+
+    /// Values for the symbols, in the order they were made, that take some
+    /// path to a halt meeting `goal`. Paths that fail or starve are skipped.
+    pub fn solve<S: Solver>(
+        &self,
+        goal: Goal,
+        solver: &S,
+    ) -> Result<Option<Vec<i128>>, SymbolicError> {
+        for p in self.explore()? {
+            if p.end != Some(End::Halted) {
+                continue;
+            }
+            let (target, value) = match goal {
+                Goal::Cell(a, v) => (p.get(a), v),
+                Goal::Output(n, v) => (p.outputs.get(n).cloned().flatten(), v),
+            };
+            let target = match target {
+                Some(t) => t,
+                None => continue,
+            };
+            let mut constraints = p.constraints.clone();
+            constraints.push(Constraint {
+                lhs: target,
+                rel: Rel::Eq,
+                rhs: Expr::constant(value),
+            });
+            if let Some(values) = solver.solve(&self.problem(constraints))? {
+                return Ok(Some(values));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DAY5;
+    use crate::{assemble, Linear, Program, State};
+
+    #[test]
+    fn test_expr() {
+        let (x, y) = (Expr::symbol(0), Expr::symbol(1));
+        let e = x.mul(&Expr::constant(3)).add(&y).add(&Expr::constant(-2));
+
+        assert_eq!(3 * 5 + 7 - 2, e.eval(&[5, 7]));
+        assert_eq!("x1 + 3*x0 + -2", e.to_string());
+        assert_eq!(None, e.as_constant());
+        assert_eq!(Some(4), e.sub(&e).add(&Expr::constant(4)).as_constant());
+        assert!(e.linear().is_some());
+        assert!(x.mul(&y).linear().is_none());
+    }
+
+    #[test]
+    fn test_cell() {
+        // [0] = [9] * [10] + 3
+        let t = [2, 9, 10, 0, 1001, 0, 3, 0, 99];
+        let mut s = Symbolic::new(&t);
+        let a = s.symbol("a", 0..=20);
+        let b = s.symbol("b", 0..=20);
+        s.set(9, a);
+        s.set(10, b);
+        let paths = s.explore().unwrap();
+
+        assert_eq!(1, paths.len());
+        assert_eq!(Some(End::Halted), paths[0].end);
+        assert_eq!(Some(7 * 9 + 3), paths[0].get(0).map(|e| e.eval(&[7, 9])));
+        // nonlinear, so every pair is tried
+        let found = s.solve(Goal::Cell(0, 66), &Linear).unwrap().unwrap();
+        assert_eq!(63, found[0] * found[1]);
+        assert_eq!(None, s.solve(Goal::Cell(0, 1000), &Linear).unwrap());
+    }
+
+    #[test]
+    fn test_branches() {
+        // day5's larger example with a symbolic input: 999, 1000 or 1001
+        let mut s = Symbolic::new(&DAY5);
+        let x = s.symbol("x", -100..=100);
+        s.input(x);
+
+        assert_eq!(3, s.explore().unwrap().len());
+        for (out, check) in &[(999, 7), (1000, 8), (1001, 9)] {
+            let found = s.solve(Goal::Output(0, *out), &Linear).unwrap().unwrap();
+            let mut p = Program::new(&DAY5);
+            p.inputs.push_back(found[0]);
+            let mut q = Program::new(&DAY5);
+            q.inputs.push_back(*check);
+            assert_eq!(q.run(), p.run());
+        }
+    }
+
+    #[test]
+    fn test_compare_without_wrapping() {
+        // MAX - -1 wraps negative, but MAX still isn't less than -1
+        let t = [1107, i128::MAX, -1, 0, 4, 0, 99];
+        let paths = Symbolic::new(&t).explore().unwrap();
+        assert_eq!(Ok(State::Output(0)), Program::new(&t).run());
+        assert_eq!(vec![Some(Expr::constant(0))], paths[0].outputs);
+
+        // outputs whether the input is less than -1
+        let t = [3, 9, 1007, 9, -1, 10, 4, 10, 99, 0, 0];
+        let mut s = Symbolic::new(&t);
+        let x = s.symbol("x", i128::MAX - 5..=i128::MAX);
+        s.input(x);
+        assert_eq!(Ok(None), s.solve(Goal::Output(0, 1), &Linear));
+        assert!(s.solve(Goal::Output(0, 0), &Linear).unwrap().is_some());
+
+        // a domain of every i128 is too big to rule paths out, so both stay
+        let mut s = Symbolic::new(&t);
+        let x = s.symbol("x", i128::MIN..=i128::MAX);
+        s.input(x);
+        assert_eq!(2, s.explore().unwrap().len());
+    }
+
+    #[test]
+    fn test_infeasible_paths() {
+        // counts [x] down to zero, so it loops once per value x can take
+        let t = assemble(
+            "
+            loop:   jf [x], #end
+                    add [x], #-1, [x]
+                    jt #1, #loop
+            end:    hlt
+            x:      db 0
+            ",
+        )
+        .unwrap();
+        let mut s = Symbolic::new(&t);
+        let x = s.symbol("x", 0..=5);
+        s.set(11, x);
+        let paths = s.explore().unwrap();
+
+        assert_eq!(6, paths.len());
+        assert!(paths.iter().all(|p| p.end == Some(End::Halted)));
+        // one path per value, each going round the loop that many times
+        let mut counts: Vec<usize> = paths.iter().map(|p| p.count).collect();
+        counts.sort_unstable();
+        assert_eq!(vec![1, 4, 7, 10, 13, 16], counts);
+    }
+
+    #[test]
+    fn test_errors() {
+        // writes through a symbolic address
+        let mut s = Symbolic::new(&[1101, 1, 1, 0, 99]);
+        let a = s.symbol("a", 0..=9);
+        s.set(3, a);
+        let paths = s.explore().unwrap();
+        assert_eq!(
+            Some(End::Failed(SymbolicError::SymbolicAddress { ip: 0 })),
+            paths[0].end
+        );
+
+        // every input doubles the paths
+        let mut s = Symbolic::new(&[3, 9, 1005, 9, 0, 1105, 1, 0, 99, 0]);
+        s.max_paths = 8;
+        for n in 0..10 {
+            let x = s.symbol(&format!("x{}", n), 0..=1);
+            s.input(x);
+        }
+        assert_eq!(Err(SymbolicError::TooManyPaths { limit: 8 }), s.explore());
+
+        // the relative base overflows the same way it does for `Program`
+        for t in [[109, i128::MAX, 109, 1, 99], [109, i128::MAX, 204, 1, 99]] {
+            let paths = Symbolic::new(&t).explore().unwrap();
+            let e = IntcodeError::Overflow { ip: 2 };
+            assert_eq!(Err(e.clone()), Program::new(&t).run());
+            assert_eq!(Some(End::Failed(e.into())), paths[0].end);
+        }
+
+        // an `out` in the top cells has nowhere to go next
+        let mut s = Symbolic::new(&[1105, 1, i128::MAX - 1]);
+        s.set(i128::MAX - 1, Expr::constant(104));
+        s.set(i128::MAX, Expr::constant(7));
+        let e = IntcodeError::Overflow { ip: i128::MAX - 1 };
+        let paths = s.explore().unwrap();
+        assert_eq!(Some(End::Failed(e.into())), paths[0].end);
+        assert!(paths[0].outputs.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crossbeam_channel::{select, unbounded, Receiver, Sender};
+
+use crate::{IntcodeError, Pipeline, PipelineError, Program, State};
+
+// What every node thread needs to agree on to tell a deadlock from a node
+// that's just slow.
+struct Shared {
+    // Nodes not blocked on input.
+    running: usize,
+    // For each channel something still reads: (readers, values in flight).
+    read: HashMap<String, (usize, usize)>,
+    last: HashMap<String, i128>,
+    // Dropped to wake every blocked node once nothing can make progress.
+    stop: Option<Sender<()>>,
+}
+
+impl Shared {
+    fn sent(&mut self, c: &str, x: i128) {
+        if let Some(r) = self.read.get_mut(c) {
+            r.1 += 1;
+        }
+        self.last.insert(c.to_string(), x);
+    }
+
+    fn block(&mut self) {
+        self.running -= 1;
+        self.check();
+    }
+
+    fn wake(&mut self, c: &str) {
+        self.running += 1;
+        self.read.get_mut(c).unwrap().1 -= 1;
+    }
+
+    fn leave(&mut self, input: Option<&String>, running: bool) {
+        if running {
+            self.running -= 1;
+        }
+        if let Some(c) = input {
+            let r = self.read.get_mut(c).unwrap();
+            r.0 -= 1;
+            if r.0 == 0 {
+                self.read.remove(c);
+            }
+        }
+        self.check();
+    }
+
+    fn check(&mut self) {
+        if self.running == 0 && self.read.values().all(|r| r.1 == 0) {
+            self.stop.take();
+        }
+    }
+}
+
+// Runs a node until it halts, fails, or can never get more input. Returns
+// whether it halted.
+fn worker(
+    mut program: Program,
+    input: Option<(String, Receiver<i128>)>,
+    outputs: Vec<(String, Sender<i128>)>,
+    stop: Receiver<()>,
+    shared: Arc<Mutex<Shared>>,
+) -> (Program, Result<bool, IntcodeError>) {
+    let mut running = true;
+    let end = loop {
+        match program.run() {
+            Err(e) => break Err(e),
+            Ok(State::Halted) => break Ok(true),
+            Ok(State::Output(o)) => {
+                let mut s = shared.lock().unwrap();
+                for (c, tx) in &outputs {
+                    s.sent(c, o);
+                    // the pipeline holds a receiver for every channel, so
+                    // this can't fail
+                    tx.send(o).unwrap();
+                }
+            }
+            Ok(State::NeedsInput) => {
+                let (c, rx) = match &input {
+                    Some(i) => i,
+                    None => break Ok(false),
+                };
+                shared.lock().unwrap().block();
+                running = false;
+                select! {
+                    recv(rx) -> x => match x {
+                        Ok(x) => {
+                            shared.lock().unwrap().wake(c);
+                            running = true;
+                            program.inputs.push_back(x);
+                        }
+                        Err(_) => break Ok(false),
+                    },
+                    recv(stop) -> _ => break Ok(false),
+                }
+            }
+        }
+    };
+    // Closing our outputs before leaving lets anything waiting only on us
+    // see that nothing more is coming.
+    drop(outputs);
+    shared
+        .lock()
+        .unwrap()
+        .leave(input.as_ref().map(|i| &i.0), running);
+    (program, end)
+}
+
+impl Pipeline {
+    /// Like `run`, but each node gets its own thread and blocks on its
+    /// input channel.
+    ///
+    /// A node that halts closes the channels it writes, and a node waiting
+    /// on a closed, empty channel stops. If every node left is waiting and
+    /// nothing is in flight to any of them, they're all stopped. Nodes that
+    /// stop without halting are reported as starved, as with `run`.
+    pub fn run_threaded(&mut self) -> Result<(), PipelineError> {
+        self.wire()?;
+
+        let mut senders = HashMap::new();
+        let mut receivers = HashMap::new();
+        for (name, c) in &mut self.channels {
+            let (tx, rx) = unbounded();
+            for x in c.queue.drain(..) {
+                tx.send(x).unwrap();
+            }
+            senders.insert(name.clone(), tx);
+            receivers.insert(name.clone(), rx);
+        }
+
+        let live: Vec<usize> = (0..self.nodes.len())
+            .filter(|n| !self.nodes[*n].halted)
+            .collect();
+        let mut read = HashMap::new();
+        for n in &live {
+            if let Some(c) = &self.nodes[*n].input {
+                let r = read.entry(c.clone()).or_insert((0, receivers[c].len()));
+                r.0 += 1;
+            }
+        }
+        let (stop_tx, stop_rx) = unbounded();
+        let shared = Arc::new(Mutex::new(Shared {
+            running: live.len(),
+            read,
+            last: HashMap::new(),
+            stop: Some(stop_tx),
+        }));
+
+        let mut handles = Vec::new();
+        for n in live {
+            let node = &self.nodes[n];
+            let input = node
+                .input
+                .as_ref()
+                .map(|c| (c.clone(), receivers[c].clone()));
+            let outputs = node
+                .outputs
+                .iter()
+                .map(|c| (c.clone(), senders[c].clone()))
+                .collect();
+            let program = node.program.clone();
+            let (stop, shared) = (stop_rx.clone(), shared.clone());
+            let h = thread::spawn(move || worker(program, input, outputs, stop, shared));
+            handles.push((n, h));
+        }
+        drop(senders);
+
+        let mut error = None;
+        for (n, h) in handles {
+            let (program, end) = h.join().unwrap();
+            let node = &mut self.nodes[n];
+            node.program = program;
+            match end {
+                Ok(halted) => node.halted = halted,
+                Err(e) => {
+                    error = error.or_else(|| {
+                        Some(PipelineError::Intcode {
+                            node: node.name.clone(),
+                            error: e,
+                        })
+                    })
+                }
+            }
+        }
+
+        let shared = shared.lock().unwrap();
+        for (name, c) in &mut self.channels {
+            c.queue.extend(receivers[name].try_iter());
+            if let Some(x) = shared.last.get(name) {
+                c.last = Some(*x);
+            }
+        }
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+        let starved: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|n| !n.halted)
+            .map(|n| n.name.clone())
+            .collect();
+        if starved.is_empty() {
+            Ok(())
+        } else {
+            Err(PipelineError::Deadlock { starved })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{link, permutations};
+
+    const AMPS: [&str; 5] = ["A", "B", "C", "D", "E"];
+
+    fn amplifiers(t: &[i128], phases: &[i128]) -> Pipeline {
+        let mut p = Pipeline::new();
+        for (name, x) in AMPS.iter().zip(phases) {
+            p.node(name, Program::new(t)).phase(name, *x);
+        }
+        p.ring(&AMPS).send(&link("E", "A"), 0);
+        p
+    }
+
+    // Runs every phase order both ways and returns the best signal.
+    fn compare(t: &[i128], phases: &[i128]) -> i128 {
+        let mut best = 0;
+        for order in permutations(phases) {
+            let mut single = amplifiers(t, &order);
+            let mut threaded = single.clone();
+            assert_eq!(Ok(()), single.run());
+            assert_eq!(Ok(()), threaded.run_threaded());
+
+            let out = single.last(&link("E", "A")).unwrap();
+            assert_eq!(Some(out), threaded.last(&link("E", "A")), "{:?}", order);
+            best = best.max(out);
+        }
+        best
+    }
+
+    #[test]
+    fn test_day7_one() {
+        let t = [
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
+            99, 0, 0,
+        ];
+        assert_eq!(54321, compare(&t, &[0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn test_day7_two() {
+        let t = [
+            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
+            -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
+            53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+        ];
+        assert_eq!(18216, compare(&t, &[5, 6, 7, 8, 9]));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let echo = [3, 7, 4, 7, 1105, 1, 0, 0];
+        let mut single = Pipeline::new();
+        for name in &AMPS {
+            single.node(name, Program::new(&echo));
+        }
+        single.ring(&AMPS);
+        let mut threaded = single.clone();
+
+        let starved = AMPS.iter().map(|s| s.to_string()).collect();
+        assert_eq!(Err(PipelineError::Deadlock { starved }), single.run());
+        assert_eq!(single.run(), threaded.run_threaded());
+    }
+
+    #[test]
+    fn test_upstream_halts() {
+        let mut p = Pipeline::new();
+        p.node("src", Program::new(&[104, 1, 99]))
+            .node("sink", Program::new(&[3, 9, 3, 9, 4, 9, 99, 0, 0, 0]))
+            .chain(&["src", "sink"]);
+
+        assert_eq!(
+            Err(PipelineError::Deadlock {
+                starved: vec!["sink".to_string()]
+            }),
+            p.run_threaded()
+        );
+    }
+}
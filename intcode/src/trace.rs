@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{Read, Write};
+
+use crate::instruction::{Instruction, Mode, Op};
+use crate::{varint, Program, State, Word};
+
+const MAGIC: &[u8; 4] = b"ICT1";
+
+const WRITE: i128 = 1;
+const RB: i128 = 2;
+const INPUT: i128 = 4;
+const OUTPUT: i128 = 8;
+
+/// One executed instruction. `operands` holds the value each parameter
+/// resolved to, except the destination, which holds the address written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub ip: i128,
+    pub opcode: i128,
+    pub operands: Vec<i128>,
+    pub write: Option<(i128, i128)>,
+    pub rb: Option<i128>,
+    pub input: Option<i128>,
+    pub output: Option<i128>,
+}
+
+impl Event {
+    /// Where execution continued after this instruction, or `None` for an
+    /// event that couldn't have run: an unknown opcode, the wrong number of
+    /// operands, or an ip at the very top of memory.
+    pub fn next(&self) -> Option<i128> {
+        let op = Op::from_code(self.opcode % 100)?;
+        if self.operands.len() != op.params() {
+            return None;
+        }
+        match op {
+            Op::Jt if self.operands[0] != 0 => Some(self.operands[1]),
+            Op::Jf if self.operands[0] == 0 => Some(self.operands[1]),
+            _ => self.ip.checked_add(1 + op.params() as i128),
+        }
+    }
+}
+
+/// Every instruction a `Program` executed since tracing was switched on,
+/// along with the memory and registers at that point so any step can be
+/// reconstructed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub memory: Vec<(i128, i128)>,
+    pub ip: i128,
+    pub rb: i128,
+    pub events: Vec<Event>,
+}
+
+impl Trace {
+    /// Records the VM's starting state. Values too wide for `i128` are
+    /// saturated.
+    pub fn new<W: Word>(p: &Program<W>) -> Trace {
+        Trace {
+            memory: p
+                .cells()
+                .into_iter()
+                .map(|(a, v)| (a, v.saturating_i128()))
+                .collect(),
+            ip: p.i,
+            rb: p.rb,
+            events: Vec::new(),
+        }
+    }
+
+    /// Rebuilds the VM as it was after the first `n` events. Input the
+    /// program went on to read is queued, so running the result retraces
+    /// the rest of the recording.
+    pub fn replay(&self, n: usize) -> Program {
+        let n = n.min(self.events.len());
+        let mut memory: BTreeMap<i128, i128> = self.memory.iter().copied().collect();
+        let mut rb = self.rb;
+        for e in &self.events[..n] {
+            if let Some((a, v)) = e.write {
+                memory.insert(a, v);
+            }
+            if let Some(r) = e.rb {
+                rb = r;
+            }
+        }
+
+        let mut p = Program::new(&[]);
+        for (a, v) in memory {
+            p.set(a, v);
+        }
+        p.i = match n {
+            0 => self.ip,
+            // one that couldn't have run leaves ip where it was
+            _ => {
+                let e = &self.events[n - 1];
+                e.next().unwrap_or(e.ip)
+            }
+        };
+        p.rb = rb;
+        p.inputs = self.events[n..].iter().filter_map(|e| e.input).collect();
+        p
+    }
+
+    /// The output produced by the first `n` events.
+    pub fn outputs(&self, n: usize) -> Vec<i128> {
+        self.events
+            .iter()
+            .take(n)
+            .filter_map(|e| e.output)
+            .collect()
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        varint::write(w, self.memory.len() as i128)?;
+        for (a, v) in &self.memory {
+            varint::write(w, *a)?;
+            varint::write(w, *v)?;
+        }
+        varint::write(w, self.ip)?;
+        varint::write(w, self.rb)?;
+
+        for e in &self.events {
+            let mut flags = 0;
+            if e.write.is_some() {
+                flags |= WRITE;
+            }
+            if e.rb.is_some() {
+                flags |= RB;
+            }
+            if e.input.is_some() {
+                flags |= INPUT;
+            }
+            if e.output.is_some() {
+                flags |= OUTPUT;
+            }
+            varint::write(w, flags)?;
+            varint::write(w, e.ip)?;
+            varint::write(w, e.opcode)?;
+            for x in &e.operands {
+                varint::write(w, *x)?;
+            }
+            if let Some((a, v)) = e.write {
+                varint::write(w, a)?;
+                varint::write(w, v)?;
+            }
+            for x in e.rb.iter().chain(&e.input).chain(&e.output) {
+                varint::write(w, *x)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Trace> {
+        let bad = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(bad("not an Intcode trace"));
+        }
+        let cells = varint::read_size(r, "cell count")?;
+        let mut memory = Vec::new();
+        for _ in 0..cells {
+            memory.push((varint::read_one(r)?, varint::read_one(r)?));
+        }
+        let ip = varint::read_one(r)?;
+        let rb = varint::read_one(r)?;
+
+        let mut events = Vec::new();
+        while let Some(flags) = varint::read(r)? {
+            let ip = varint::read_one(r)?;
+            let opcode = varint::read_one(r)?;
+            let op = Op::from_code(opcode % 100).ok_or_else(|| bad("bad opcode in trace"))?;
+            let mut operands = Vec::new();
+            for _ in 0..op.params() {
+                operands.push(varint::read_one(r)?);
+            }
+            let write = if flags & WRITE != 0 {
+                Some((varint::read_one(r)?, varint::read_one(r)?))
+            } else {
+                None
+            };
+            let mut field = |bit: i128| -> io::Result<Option<i128>> {
+                if flags & bit != 0 {
+                    Ok(Some(varint::read_one(r)?))
+                } else {
+                    Ok(None)
+                }
+            };
+            let rb = field(RB)?;
+            let input = field(INPUT)?;
+            let output = field(OUTPUT)?;
+            events.push(Event {
+                ip,
+                opcode,
+                operands,
+                write,
+                rb,
+                input,
+                output,
+            });
+        }
+
+        Ok(Trace {
+            memory,
+            ip,
+            rb,
+            events,
+        })
+    }
+}
+
+/// The index of the first event where two traces disagree, or `None` if
+/// they're identical. Traces that start from different states diverge at 0.
+pub fn diverge(a: &Trace, b: &Trace) -> Option<usize> {
+    if a.memory != b.memory || a.ip != b.ip || a.rb != b.rb {
+        return Some(0);
+    }
+    (0..a.events.len().max(b.events.len())).find(|n| a.events.get(*n) != b.events.get(*n))
+}
+
+// Called before a traced instruction runs, while the operands can still be
+// read as the instruction sees them. There's no event for an instruction
+// that can't run, so the error comes from executing it.
+pub(crate) fn begin<W: Word>(p: &Program<W>) -> Option<(Instruction, Event)> {
+    let get = |i| p.get(i).saturating_i128();
+    let ins = Instruction::decode(p.i, get).ok()?;
+    let operands = ins
+        .params()
+        .enumerate()
+        .map(|(n, (m, x))| match (m, ins.op.dest() == Some(n)) {
+            (Mode::Position, true) => Some(x),
+            (Mode::Relative, true) => p.rb.checked_add(x),
+            (Mode::Position, false) => Some(get(x)),
+            (Mode::Immediate, _) => Some(x),
+            (Mode::Relative, false) => p.rb.checked_add(x).map(get),
+        })
+        .collect::<Option<_>>()?;
+    let event = Event {
+        ip: p.i,
+        opcode: get(p.i),
+        operands,
+        write: None,
+        rb: None,
+        input: None,
+        output: None,
+    };
+    Some((ins, event))
+}
+
+pub(crate) fn finish<W: Word>(
+    p: &Program<W>,
+    ins: Instruction,
+    mut e: Event,
+    rb: i128,
+    s: &Option<State<W>>,
+) -> Event {
+    if let Some(d) = ins.op.dest() {
+        let a = e.operands[d];
+        let v = p.get(a).saturating_i128();
+        e.write = Some((a, v));
+        if ins.op == Op::In {
+            e.input = Some(v);
+        }
+    }
+    if p.rb != rb {
+        e.rb = Some(p.rb);
+    }
+    if let Some(State::Output(o)) = s {
+        e.output = Some(o.saturating_i128());
+    }
+    e
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::QUINE;
+
+    fn traced(t: &[i128], inputs: &[i128]) -> Program {
+        let mut p = Program::new(t);
+        p.inputs.extend(inputs);
+        p.start_trace();
+        while p.run().unwrap() != State::Halted {}
+        p
+    }
+
+    // day9's test that reads into a relative address and echoes it
+    const ECHO: [i128; 7] = [109, 1, 203, 2, 204, 2, 99];
+
+    #[test]
+    fn test_record() {
+        let p = traced(&ECHO, &[1024]);
+        let t = p.trace.unwrap();
+
+        assert_eq!(3, t.events.len());
+        assert_eq!(Some(1), t.events[0].rb);
+        assert_eq!(
+            Event {
+                ip: 2,
+                opcode: 203,
+                operands: vec![3],
+                write: Some((3, 1024)),
+                rb: None,
+                input: Some(1024),
+                output: None,
+            },
+            t.events[1]
+        );
+        assert_eq!(Some(1024), t.events[2].output);
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let t = traced(&ECHO, &[-5]).trace.unwrap();
+        let mut buf = Vec::new();
+        t.write(&mut buf).unwrap();
+
+        assert_eq!(t, Trace::read(&mut &buf[..]).unwrap());
+        assert!(Trace::read(&mut &buf[1..]).is_err());
+
+        // a negative cell count
+        let mut buf = MAGIC.to_vec();
+        varint::write(&mut buf, -1).unwrap();
+        let e = Trace::read(&mut &buf[..]).unwrap_err();
+        assert_eq!(
+            (io::ErrorKind::InvalidData, "bad cell count -1".to_string()),
+            (e.kind(), e.to_string())
+        );
+    }
+
+    #[test]
+    fn test_next() {
+        let mut e = traced(&ECHO, &[1]).trace.unwrap().events[0].clone();
+        assert_eq!(Some(2), e.next());
+        e.opcode = 42;
+        assert_eq!(None, e.next());
+        e.opcode = 109;
+        e.operands.clear();
+        assert_eq!(None, e.next());
+    }
+
+    #[test]
+    fn test_replay() {
+        let t = traced(&QUINE, &[]).trace.unwrap();
+
+        let mut p = t.replay(20);
+        let mut out = t.outputs(20);
+        while let State::Output(o) = p.run().unwrap() {
+            out.push(o);
+        }
+        assert_eq!(QUINE.to_vec(), out);
+    }
+
+    #[test]
+    fn test_diverge() {
+        let a = traced(&ECHO, &[1]).trace.unwrap();
+        let b = traced(&ECHO, &[2]).trace.unwrap();
+
+        assert_eq!(None, diverge(&a, &a));
+        assert_eq!(Some(1), diverge(&a, &b));
+    }
+}
@@ -0,0 +1,89 @@
+use std::convert::TryFrom;
+use std::io;
+use std::io::{Read, Write};
+
+// Signed LEB128 via zigzag, so small negative numbers stay small.
+
+pub fn write<W: Write>(w: &mut W, x: i128) -> io::Result<()> {
+    let mut z = ((x << 1) ^ (x >> 127)) as u128;
+    loop {
+        let b = (z & 0x7f) as u8;
+        z >>= 7;
+        if z == 0 {
+            return w.write_all(&[b]);
+        }
+        w.write_all(&[b | 0x80])?;
+    }
+}
+
+/// Reads one number, or `None` at a clean end of input.
+pub fn read<R: Read>(r: &mut R) -> io::Result<Option<i128>> {
+    let mut z: u128 = 0;
+    let mut shift = 0;
+    let mut b = [0];
+    loop {
+        if r.read(&mut b)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated varint",
+            ));
+        }
+        if shift >= 128 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "varint too long",
+            ));
+        }
+        z |= ((b[0] & 0x7f) as u128) << shift;
+        shift += 7;
+        if b[0] & 0x80 == 0 {
+            return Ok(Some((z >> 1) as i128 ^ -((z & 1) as i128)));
+        }
+    }
+}
+
+pub fn read_one<R: Read>(r: &mut R) -> io::Result<i128> {
+    read(r)?.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of file"))
+}
+
+/// Reads a count or length, which can't be negative; `what` names it in
+/// the error.
+pub fn read_size<R: Read>(r: &mut R, what: &str) -> io::Result<usize> {
+    let x = read_one(r)?;
+    usize::try_from(x)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("bad {} {}", what, x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let xs = [
+            0,
+            1,
+            -1,
+            63,
+            -64,
+            64,
+            1125899906842624,
+            i128::MAX,
+            i128::MIN,
+        ];
+        let mut buf = Vec::new();
+        for x in &xs {
+            write(&mut buf, *x).unwrap();
+        }
+        assert_eq!(&[0, 2, 1, 126, 127], &buf[..5]);
+
+        let mut r = &buf[..];
+        for x in &xs {
+            assert_eq!(Some(*x), read(&mut r).unwrap());
+        }
+        assert_eq!(None, read(&mut r).unwrap());
+    }
+}
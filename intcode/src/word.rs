@@ -0,0 +1,107 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+/// An integer type a `Program` can compute in. Addresses, the instruction
+/// pointer and the relative base stay `i128` whatever the word type is.
+pub trait Word:
+    Clone + PartialEq + PartialOrd + fmt::Debug + fmt::Display + Send + 'static
+{
+    fn from_i128(x: i128) -> Option<Self>;
+    fn to_i128(&self) -> Option<i128>;
+    fn checked_add(&self, o: &Self) -> Option<Self>;
+    fn checked_mul(&self, o: &Self) -> Option<Self>;
+    fn wrapping_add(&self, o: &Self) -> Self;
+    fn wrapping_mul(&self, o: &Self) -> Self;
+
+    fn zero() -> Self {
+        Self::from_i128(0).unwrap()
+    }
+
+    fn one() -> Self {
+        Self::from_i128(1).unwrap()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+
+    /// The nearest `i128`, for tooling that only deals in `i128`.
+    fn saturating_i128(&self) -> i128 {
+        match self.to_i128() {
+            Some(x) => x,
+            None if *self < Self::zero() => i128::MIN,
+            None => i128::MAX,
+        }
+    }
+}
+
+macro_rules! primitive_word {
+    ($($t:ty),*) => {$(
+        impl Word for $t {
+            fn from_i128(x: i128) -> Option<$t> {
+                <$t>::try_from(x).ok()
+            }
+
+            fn to_i128(&self) -> Option<i128> {
+                Some(*self as i128)
+            }
+
+            fn checked_add(&self, o: &$t) -> Option<$t> {
+                <$t>::checked_add(*self, *o)
+            }
+
+            fn checked_mul(&self, o: &$t) -> Option<$t> {
+                <$t>::checked_mul(*self, *o)
+            }
+
+            fn wrapping_add(&self, o: &$t) -> $t {
+                <$t>::wrapping_add(*self, *o)
+            }
+
+            fn wrapping_mul(&self, o: &$t) -> $t {
+                <$t>::wrapping_mul(*self, *o)
+            }
+        }
+    )*};
+}
+
+primitive_word!(i32, i64, i128);
+
+impl Word for BigInt {
+    fn from_i128(x: i128) -> Option<BigInt> {
+        Some(BigInt::from(x))
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        ToPrimitive::to_i128(self)
+    }
+
+    fn checked_add(&self, o: &BigInt) -> Option<BigInt> {
+        Some(self + o)
+    }
+
+    fn checked_mul(&self, o: &BigInt) -> Option<BigInt> {
+        Some(self * o)
+    }
+
+    fn wrapping_add(&self, o: &BigInt) -> BigInt {
+        self + o
+    }
+
+    fn wrapping_mul(&self, o: &BigInt) -> BigInt {
+        self * o
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+}
+
+/// Converts an `i128` program to a narrower word, or `None` if some value
+/// doesn't fit.
+pub fn convert<W: Word>(t: &[i128]) -> Option<Vec<W>> {
+    t.iter().map(|x| W::from_i128(*x)).collect()
+}